@@ -1,74 +1,187 @@
-use std::ops::Range;
+use std::{
+    collections::HashMap,
+    ops::Range,
+    time::{Duration, Instant},
+};
 
 use combine::stream::PointerOffset;
-use num::{FromPrimitive, Zero};
+use num::{BigInt, Float, FromPrimitive, Integer, One, Signed, Zero};
 use num_rational::BigRational;
 use yansi::Style;
 
-use crate::{constants::VERSION, number::DecimalTuple, ui::estimate};
+use crate::{
+    constants::{
+        CANCEL_DIGITS, DEFAULT_MAX_DEPTH, DEFAULT_MAX_PERIOD, DEFAULT_WIDTH,
+        E_DIGITS, MAX_DIGITS, MAX_FACTORIAL, MAX_FACTORIAL_ERROR, PI_DIGITS,
+        VERSION,
+    },
+    number::{ilog10, DecimalTuple},
+    ui::{estimate, palette},
+    utils::{translate_range, StrPaint},
+};
 
-#[derive(Clone, Copy, Default, Eq, PartialEq)]
-struct EstimateContext {
-    literal: bool,
-    paren: bool,
-    binary: bool,
-}
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct EstimateContext(u32);
 
 const CTX_LIT: u32 = 1 << 0;
 const CTX_PAR: u32 = 1 << 1;
 const CTX_BIN: u32 = 1 << 2;
+const CTX_CF: u32 = 1 << 3;
+const CTX_ULP: u32 = 1 << 4;
+const CTX_HEX: u32 = 1 << 5;
+const CTX_ERR: u32 = 1 << 6;
+const CTX_MIXED: u32 = 1 << 7;
+const CTX_PERIOD: u32 = 1 << 8;
+const CTX_CANCEL: u32 = 1 << 9;
+const CTX_GROUP: u32 = 1 << 10;
+const CTX_SCI: u32 = 1 << 11;
+const CTX_NEG: u32 = 1 << 12;
+const CTX_CALL: u32 = 1 << 13;
+const CTX_EACH: u32 = CTX_LIT | CTX_PAR | CTX_BIN | CTX_NEG | CTX_CALL;
+
+/// The values of `estimate=` with their bits and help, each of which also
+/// takes `+` or `-`. The kinds of subexpressions, within `each`, can also be
+/// given bare to select only them.
+pub const ESTIMATE_FLAGS: [(&str, u32, &str); 15] = [
+    ("lit", CTX_LIT, "Literals"),
+    ("par", CTX_PAR, "Parenthesized expressions"),
+    ("bin", CTX_BIN, "Binary operations and factorials, e.g. 5!"),
+    ("neg", CTX_NEG, "Negations without parentheses, e.g. -x"),
+    ("call", CTX_CALL, "Function calls, e.g. sqrt(x)"),
+    ("each", CTX_EACH, "All of the above"),
+    ("cf", CTX_CF, "Show the continued fraction of the truth"),
+    ("ulp", CTX_ULP, "Show the floats right below and above the truth"),
+    ("hex", CTX_HEX, "Show the float in the hexadecimal form"),
+    ("err", CTX_ERR, "Show the error in ppm and correct digits"),
+    ("mixed", CTX_MIXED, "Show the truth as a mixed number, e.g. 2 1/3"),
+    ("period", CTX_PERIOD, "Show the length of the repeating digits"),
+    ("cancel", CTX_CANCEL, "Warn of digits lost in + and - by cancellation"),
+    ("group", CTX_GROUP, "Group the integer digits of the truth by three"),
+    ("sci", CTX_SCI, "Show a very large or small truth as 1.5e-12"),
+];
+
+/// Whether the flag of `bit` can be given without a sign.
+pub fn is_bare_estimate(bit: u32) -> bool { bit & !CTX_EACH == 0 }
 
 impl EstimateContext {
     pub fn update(&mut self, arg: Vec<String>) {
         for arg in arg {
             for s in arg.split(",").map(|s| s.trim()) {
-                match s {
-                    "lit" => self.set_bits(CTX_LIT),
-                    "par" => self.set_bits(CTX_PAR),
-                    "bin" => self.set_bits(CTX_BIN),
-                    "+lit" => self.set_bits(self.get_bits() | CTX_LIT),
-                    "+par" => self.set_bits(self.get_bits() | CTX_PAR),
-                    "+bin" => self.set_bits(self.get_bits() | CTX_BIN),
-                    "-lit" => self.set_bits(self.get_bits() | !CTX_LIT),
-                    "-par" => self.set_bits(self.get_bits() | !CTX_PAR),
-                    "-bin" => self.set_bits(self.get_bits() | !CTX_BIN),
-                    "each" | "+each" => self.set_bits(!0),
-                    "-each" => self.set_bits(0),
+                let (sign, name) = match s.strip_prefix(['+', '-']) {
+                    Some(name) => (&s[..1], name),
+                    None => ("", s),
+                };
+                let bit = ESTIMATE_FLAGS
+                    .iter()
+                    .find(|&&(key, ..)| key == name)
+                    .map(|&(_, bit, _)| bit);
+                match (sign, bit) {
+                    ("+", Some(bit)) => self.0 |= bit,
+                    ("-", Some(bit)) => self.0 &= !bit,
+                    ("", Some(bit)) if is_bare_estimate(bit) => {
+                        self.0 = (self.0 & !CTX_EACH) | bit
+                    }
                     _ => eprintln!("unexpected value: {s}"),
                 }
             }
         }
     }
 
-    fn set_bits(&mut self, bits: u32) {
-        self.literal = bits & CTX_LIT != 0;
-        self.paren = bits & CTX_PAR != 0;
-        self.binary = bits & CTX_BIN != 0;
-    }
+    fn has(&self, bit: u32) -> bool { self.0 & bit != 0 }
+}
 
-    fn get_bits(&self) -> u32 {
-        (CTX_LIT * self.literal as u32)
-            | (CTX_PAR * self.paren as u32)
-            | (CTX_BIN * self.binary as u32)
+impl std::fmt::Display for EstimateContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flags =
+            ESTIMATE_FLAGS.iter().filter(|&&(_, bit, _)| bit.is_power_of_two());
+        for (i, &(name, bit, _)) in flags.enumerate() {
+            let sgn = if self.has(bit) { '+' } else { '-' };
+            let sep = if i > 0 { "," } else { "" };
+            write!(f, "{sep}{sgn}{name}")?;
+        }
+        Ok(())
     }
 }
 
-impl std::fmt::Display for EstimateContext {
+/// How the reports are rendered.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// Colored panels on stderr.
+    #[default]
+    Pretty,
+    /// Color-free markdown tables on stdout.
+    Markdown,
+    /// One JSON object per statement on stdout, with the numbers as strings.
+    Json,
+}
+
+impl std::fmt::Display for OutputFormat {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let sgn = |o| if o { '+' } else { '-' };
-        write!(
-            f,
-            "{}lit,{}par,{}bin",
-            sgn(self.literal),
-            sgn(self.paren),
-            sgn(self.binary)
-        )
+        match self {
+            OutputFormat::Pretty => write!(f, "pretty"),
+            OutputFormat::Markdown => write!(f, "markdown"),
+            OutputFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(OutputFormat::Pretty),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown format: {s}")),
+        }
     }
 }
 
-#[derive(Clone, Copy, Default, Eq, PartialEq)]
+/// Floating-point types evaluated along with the truth.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Precision {
+    #[default]
+    F64,
+    /// `f32` in addition to `f64`.
+    F32,
+}
+
+/// Unit of the relative error on the `error:` line.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorUnit {
+    Ratio,
+    Percent,
+    /// Representable steps from the correctly rounded value.
+    Ulp,
+    /// `20 * log10(|rel|)`.
+    Db,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
 pub struct EvalOptions {
     estimate: EstimateContext,
+    silent: bool,
+    format: OutputFormat,
+    rounding: Option<usize>,
+    max_terms: Option<usize>,
+    width: Option<usize>,
+    strict_literals: bool,
+    error_unit: Option<ErrorUnit>,
+    precision: Precision,
+    timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    digit_limit: Option<usize>,
+    base: Option<u32>,
+    mixed_fractions: bool,
+    group_repeats: bool,
+    dyadic: bool,
+    compact_zero: bool,
+    digits: Option<usize>,
+    max_period: Option<usize>,
+    group_separator: Option<char>,
+    timings: bool,
+    max_depth: Option<usize>,
+    estimate_depth: Option<usize>,
 }
 
 impl EvalOptions {
@@ -82,6 +195,181 @@ impl EvalOptions {
         self.estimate.update(arg);
     }
 
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+    pub fn format(&self) -> OutputFormat { self.format }
+
+    /// The current value of `estimate=`, e.g. `+lit,-par,...`.
+    pub fn estimate(&self) -> String { self.estimate.to_string() }
+
+    /// Shows the truncated and the rounded truth at `places` fractional
+    /// digits, at most `MAX_DIGITS`.
+    pub fn with_rounding(mut self, places: Option<usize>) -> Self {
+        self.rounding = places.map(|p| p.min(MAX_DIGITS));
+        self
+    }
+    pub fn rounding(&self) -> Option<usize> { self.rounding }
+
+    /// Abbreviates the continued fractions after `n` terms.
+    pub fn with_max_terms(mut self, n: Option<usize>) -> Self {
+        self.max_terms = n;
+        self
+    }
+    pub fn max_terms(&self) -> Option<usize> { self.max_terms }
+
+    /// Width available to the reports, usually the terminal width.
+    pub fn with_width(mut self, width: Option<usize>) -> Self {
+        self.width = width;
+        self
+    }
+    /// Falls back to `DEFAULT_WIDTH` when the width is unknown.
+    pub fn width(&self) -> usize { self.width.unwrap_or(DEFAULT_WIDTH) }
+
+    /// Rejects literals with redundant leading zeros, e.g. `007`.
+    pub fn with_strict_literals(mut self, strict: bool) -> Self {
+        self.strict_literals = strict;
+        self
+    }
+    pub fn strict_literals(&self) -> bool { self.strict_literals }
+
+    /// Adds the `error:` line in the unit.
+    pub fn with_error_unit(mut self, unit: Option<ErrorUnit>) -> Self {
+        self.error_unit = unit;
+        self
+    }
+    pub fn error_unit(&self) -> Option<ErrorUnit> { self.error_unit }
+
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+    pub fn precision(&self) -> Precision { self.precision }
+
+    /// Shows at most `limit` fractional digits of the truth.
+    pub fn with_digit_limit(mut self, limit: Option<usize>) -> Self {
+        self.digit_limit = limit;
+        self
+    }
+    pub fn digit_limit(&self) -> Option<usize> { self.digit_limit }
+
+    /// Adds the `base:` line with the truth in `base` (2 to 36). A base out
+    /// of the range adds no line.
+    pub fn with_base(mut self, base: Option<u32>) -> Self {
+        self.base = base.filter(|b| (2..=36).contains(b));
+        self
+    }
+    pub fn base(&self) -> Option<u32> { self.base }
+
+    /// Follows an improper truth such as `7/2` with `= 3 1/2`.
+    pub fn with_mixed_fractions(mut self, mixed: bool) -> Self {
+        self.mixed_fractions = mixed;
+        self
+    }
+    pub fn mixed_fractions(&self) -> bool { self.mixed_fractions }
+
+    /// Collapses long runs of a digit in the truth, e.g. `1(0×8)1`.
+    pub fn with_group_repeats(mut self, group: bool) -> Self {
+        self.group_repeats = group;
+        self
+    }
+    pub fn group_repeats(&self) -> bool { self.group_repeats }
+
+    /// Separates the groups of `+group` by `sep` rather than `,`.
+    pub fn with_group_separator(mut self, sep: Option<char>) -> Self {
+        self.group_separator = sep;
+        self
+    }
+    pub fn group_separator(&self) -> char {
+        self.group_separator.unwrap_or(',')
+    }
+
+    /// Adds the `exact:` line with the float as `m * 2^e`.
+    pub fn with_dyadic(mut self, dyadic: bool) -> Self {
+        self.dyadic = dyadic;
+        self
+    }
+    pub fn dyadic(&self) -> bool { self.dyadic }
+
+    /// Writes the negative zero float as `0` rather than `-0`.
+    pub fn with_compact_zero(mut self, compact: bool) -> Self {
+        self.compact_zero = compact;
+        self
+    }
+    pub fn compact_zero(&self) -> bool { self.compact_zero }
+
+    /// Writes the truth with exactly `places` fractional digits, rounded,
+    /// instead of marking the repeating part. `places` is at most
+    /// `MAX_DIGITS`.
+    pub fn with_digits(mut self, places: Option<usize>) -> Self {
+        self.digits = places.map(|p| p.min(MAX_DIGITS));
+        self
+    }
+    pub fn digits(&self) -> Option<usize> { self.digits }
+
+    /// Truncates the truth whose period is longer than `max_period` rather
+    /// than finding the period.
+    pub fn with_max_period(mut self, max_period: Option<usize>) -> Self {
+        self.max_period = max_period;
+        self
+    }
+    /// Falls back to `DEFAULT_MAX_PERIOD` when unset.
+    pub fn max_period(&self) -> usize {
+        self.max_period.unwrap_or(DEFAULT_MAX_PERIOD)
+    }
+
+    /// Rejects the parentheses, calls and unary minuses nested deeper than
+    /// `max_depth`, rather than overflowing the stack. Chains of binary
+    /// operators, as in `1 + 2 + 3`, are not nesting.
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+    /// Falls back to `DEFAULT_MAX_DEPTH` when unset.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)
+    }
+
+    /// Estimates only the subexpressions at most `depth` deep, the whole
+    /// expression being at depth 0.
+    pub fn with_estimate_depth(mut self, depth: Option<usize>) -> Self {
+        self.estimate_depth = depth;
+        self
+    }
+    pub fn estimate_depth(&self) -> Option<usize> { self.estimate_depth }
+
+    /// Reports the time spent in parsing and evaluating each statement.
+    pub fn with_timings(mut self, timings: bool) -> Self {
+        self.timings = timings;
+        self
+    }
+
+    pub fn timings(&self) -> bool { self.timings }
+
+    /// Aborts an evaluation running longer than `timeout`.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Starts the clock for `timeout`.
+    fn started(mut self) -> Self {
+        self.deadline = self.timeout.map(|t| Instant::now() + t);
+        self
+    }
+
+    fn timed_out(&self) -> bool {
+        self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    /// Suppresses the estimate output, including the one for the whole
+    /// expression.
+    pub fn silenced(mut self) -> Self {
+        self.silent = true;
+        self
+    }
+
     pub fn update(&mut self, arg: &str) {
         for s in arg.split(";").map(|s| s.trim()) {
             let mut it = s.splitn(2, "=").map(|s| s.trim());
@@ -89,8 +377,13 @@ impl EvalOptions {
                 let rem: Vec<String> =
                     it.next().into_iter().map(|s| s.to_owned()).collect();
                 match key {
-                    "estimate" if rem.is_empty() => self.print_estimate(),
+                    "estimate" if rem.is_empty() => self.show(),
                     "estimate" => self.set_estimate(rem),
+                    "format" if rem.is_empty() => self.show(),
+                    "format" => match rem[0].parse() {
+                        Ok(format) => self.format = format,
+                        Err(e) => eprintln!("{e}"),
+                    },
                     "help" => self.help(),
                     "version" => self.version(),
                     _ => eprintln!("unexpected key: {key}"),
@@ -100,19 +393,72 @@ impl EvalOptions {
     }
 
     fn help(&self) {
+        let section = |title: &str, entries: &[(&str, &str)]| {
+            eprintln!("{}", title.bold());
+            for (key, desc) in entries {
+                eprintln!("    {key:<16}{}", desc.fg(palette().dark));
+            }
+            eprintln!();
+        };
+
+        eprintln!();
+        section(
+            "Operators:",
+            &[
+                ("+  -  *  /", "Arithmetic on exact rationals and on f64"),
+                ("%", "Floored remainder, e.g. -1 % 3 == 2"),
+                ("-x", "Negation"),
+                ("n!", "Factorial of a non-negative integer"),
+                ("2(3), (1)(2)", "Implicit multiplication"),
+            ],
+        );
+        let functions: Vec<_> =
+            FUNCTIONS.iter().map(|info| (info.usage(), info.desc)).collect();
+        let functions: Vec<_> = functions
+            .iter()
+            .map(|(usage, desc)| (usage.as_str(), *desc))
+            .collect();
+        section("Functions:", &functions);
+        section(
+            "Notation:",
+            &[
+                ("1.5e-3", "Literal with an exponent"),
+                ("0x1F, 0o17, 0b1", "Integer literals in bases 16, 8, and 2"),
+                ("50%", "Percent, unless an operand follows as in 50 % 3"),
+                ("pi, e", "Constants, whose truths are close rationals"),
+                ("0.1(6...)", "Repeating decimal 0.1666..."),
+                ("x * (1 + r)", "Float with the relative error r to the truth"),
+            ],
+        );
+        section(
+            "Commands:",
+            &[
+                (":help", "Print this message"),
+                (":version", "Print the version"),
+                (":bindings", "Print the key bindings of the line editor"),
+                (":show", "Print the current options"),
+                (":set key=value", "Set the options (also `:key=value`)"),
+                (":undo", "Revert the last assignment to a variable"),
+                (":quit, :exit", "Quit"),
+            ],
+        );
+        let estimates: Vec<_> = ESTIMATE_FLAGS
+            .iter()
+            .map(|&(name, _, help)| (format!("  {name}"), help))
+            .collect();
+        let mut options =
+            vec![("estimate=arg", "Which subexpressions to estimate")];
+        options
+            .extend(estimates.iter().map(|(key, help)| (key.as_str(), *help)));
+        options.extend([
+            ("  +x, -x", "Enable or disable x, keeping the others"),
+            ("format=arg", "Report style: pretty, markdown, or json"),
+        ]);
+        section("Options:", &options);
         eprintln!(
-            r#"
-:help       Print this message
-:version    Print the version
-:quit       Quit
-:exit       Same as :quit
-
-:estimate[=arg]
-            Which subexpressions to estimate.
-            Current value is "{}". "lit", "par", and "bin" means
-            literals, parentheses, and binary operations respectively.
-"#,
-            Style::default().bold().paint(self.estimate)
+            "Current values are \"{}\" and \"{}\".\n",
+            Style::default().bold().paint(self.estimate),
+            Style::default().bold().paint(self.format)
         );
     }
 
@@ -120,20 +466,46 @@ impl EvalOptions {
         eprintln!("v{}\n", VERSION.unwrap_or("?.?.?"));
     }
 
-    fn print_estimate(&self) {
-        eprintln!(":estimate={}\n", self.estimate);
+    pub fn show(&self) {
+        eprintln!(":estimate={}", self.estimate);
+        eprintln!(":format={}\n", self.format);
     }
 
     pub fn do_estimate(&self, ctx: &EvalContext) -> bool {
+        if self.silent {
+            return false;
+        }
         if ctx.depth == 0 {
             return true;
         }
+        if self.estimate_depth.is_some_and(|depth| ctx.depth > depth) {
+            return false;
+        }
         match ctx.expr_ty {
-            ExprTy::Literal => self.estimate.literal,
-            ExprTy::Paren => self.estimate.paren,
-            ExprTy::Binary => self.estimate.binary,
+            ExprTy::Literal => self.estimate.has(CTX_LIT),
+            ExprTy::Paren => self.estimate.has(CTX_PAR),
+            ExprTy::Binary => self.estimate.has(CTX_BIN),
+            ExprTy::Neg => self.estimate.has(CTX_NEG),
+            ExprTy::Call => self.estimate.has(CTX_CALL),
         }
     }
+
+    pub fn show_cont_frac(&self) -> bool { self.estimate.has(CTX_CF) }
+
+    pub fn show_neighbors(&self) -> bool { self.estimate.has(CTX_ULP) }
+
+    pub fn show_hex(&self) -> bool { self.estimate.has(CTX_HEX) }
+
+    pub fn show_err(&self) -> bool { self.estimate.has(CTX_ERR) }
+
+    pub fn show_mixed(&self) -> bool { self.estimate.has(CTX_MIXED) }
+    pub fn show_period(&self) -> bool { self.estimate.has(CTX_PERIOD) }
+
+    pub fn show_cancel(&self) -> bool { self.estimate.has(CTX_CANCEL) }
+
+    pub fn show_group(&self) -> bool { self.estimate.has(CTX_GROUP) }
+
+    pub fn show_sci(&self) -> bool { self.estimate.has(CTX_SCI) }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -147,38 +519,389 @@ impl LitComponent {
         Self { digits, exponent }
     }
 
-    pub fn eval(&self) -> ValueTy {
+    /// The literal divided by 100, for `50%`. `None` if the exponent
+    /// overflows.
+    pub fn percent(self) -> Option<Self> {
+        let exponent = self.exponent.checked_sub(2)?;
+        Some(Self { exponent, ..self })
+    }
+
+    /// The value of the literal at `range`, which is only for the error
+    /// when the digits are malformed.
+    pub fn eval(&self, range: Range<usize>) -> Result<ValueTy, EvalError> {
+        let bad = || EvalError::BadLiteral(range.clone());
         let rat: BigRational =
-            self.digits.parse::<DecimalTuple>().unwrap().into();
+            self.digits.parse::<DecimalTuple>().map_err(|_| bad())?.into();
         let exp = BigRational::from_i32(10).unwrap().pow(self.exponent);
-        let flt: f64 =
-            format!("{}E{}", self.digits, self.exponent).parse().unwrap();
-        (rat * exp, flt)
+        let flt: f64 = format!("{}E{}", self.digits, self.exponent)
+            .parse()
+            .map_err(|_| bad())?;
+        Ok((rat * exp, flt))
+    }
+
+    /// The literal rounded to `f32` directly, not through `f64`. NaN if the
+    /// digits are malformed, which `eval` reports.
+    pub fn eval_f32(&self) -> f32 {
+        format!("{}E{}", self.digits, self.exponent).parse().unwrap_or(f32::NAN)
+    }
+}
+
+/// A named irrational constant. Its truth is a rational close enough to be
+/// told apart from any `f64`, but not the exact value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstKind {
+    Pi,
+    E,
+}
+
+impl ConstKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ConstKind::Pi => "pi",
+            ConstKind::E => "e",
+        }
+    }
+
+    /// The truth is truncated to 60 decimal places rather than taken from
+    /// a convergent, as the decimal expansion of the latter would repeat
+    /// with an enormous period.
+    pub fn eval(self) -> ValueTy {
+        let (digits, flt) = match self {
+            ConstKind::Pi => (PI_DIGITS, std::f64::consts::PI),
+            ConstKind::E => (E_DIGITS, std::f64::consts::E),
+        };
+        (digits.parse::<DecimalTuple>().unwrap().into(), flt)
+    }
+
+    pub fn eval_f32(self) -> f32 {
+        match self {
+            ConstKind::Pi => std::f32::consts::PI,
+            ConstKind::E => std::f32::consts::E,
+        }
+    }
+}
+
+/// A function applied to expressions, e.g. `sqrt(2)` or `min(1/3, 0.3)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FnKind {
+    Sqrt,
+    Abs,
+    Min,
+    Max,
+    Gcd,
+    Lcm,
+}
+
+/// The number of arguments a function takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl std::fmt::Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exactly(n) => f.pad(&n.to_string()),
+            Arity::AtLeast(n) => f.pad(&format!("{n}+")),
+        }
+    }
+}
+
+/// An entry of `FUNCTIONS`.
+#[derive(Clone, Copy, Debug)]
+pub struct FnInfo {
+    pub kind: FnKind,
+    pub name: &'static str,
+    pub arity: Arity,
+    pub desc: &'static str,
+}
+
+impl FnInfo {
+    /// How a call looks, e.g. `gcd(x, y)` or `min(x, y, ...)`.
+    pub fn usage(&self) -> String {
+        let args = match self.arity {
+            Arity::Exactly(1) => "x",
+            Arity::Exactly(_) => "x, y",
+            Arity::AtLeast(_) => "x, y, ...",
+        };
+        format!("{}({args})", self.name)
+    }
+}
+
+/// The functions, which the parser, `:help`, and `--list-functions` go
+/// through.
+pub const FUNCTIONS: [FnInfo; 6] = [
+    FnInfo {
+        kind: FnKind::Sqrt,
+        name: "sqrt",
+        arity: Arity::Exactly(1),
+        desc: "Square root, exact for the perfect squares",
+    },
+    FnInfo {
+        kind: FnKind::Abs,
+        name: "abs",
+        arity: Arity::Exactly(1),
+        desc: "Absolute value",
+    },
+    FnInfo {
+        kind: FnKind::Min,
+        name: "min",
+        arity: Arity::AtLeast(2),
+        desc: "Minimum of the truths",
+    },
+    FnInfo {
+        kind: FnKind::Max,
+        name: "max",
+        arity: Arity::AtLeast(2),
+        desc: "Maximum of the truths",
+    },
+    FnInfo {
+        kind: FnKind::Gcd,
+        name: "gcd",
+        arity: Arity::Exactly(2),
+        desc: "GCD of integers",
+    },
+    FnInfo {
+        kind: FnKind::Lcm,
+        name: "lcm",
+        arity: Arity::Exactly(2),
+        desc: "LCM of integers",
+    },
+];
+
+impl FnKind {
+    pub fn info(self) -> &'static FnInfo {
+        FUNCTIONS.iter().find(|info| info.kind == self).unwrap()
+    }
+
+    pub fn name(self) -> &'static str { self.info().name }
+
+    /// Whether the function takes `n` arguments.
+    pub fn takes(self, n: usize) -> bool {
+        match self.info().arity {
+            Arity::Exactly(m) => n == m,
+            Arity::AtLeast(m) => n >= m,
+        }
     }
 }
 
 #[derive(Debug)]
 pub enum Expr {
     Literal(LitComponent, Range<PointerOffset<str>>),
+    Const(ConstKind, Range<PointerOffset<str>>),
     Mul(Box<Expr>, Box<Expr>, Range<PointerOffset<str>>),
     Div(Box<Expr>, Box<Expr>, Range<PointerOffset<str>>),
+    Rem(Box<Expr>, Box<Expr>, Range<PointerOffset<str>>),
     Add(Box<Expr>, Box<Expr>, Range<PointerOffset<str>>),
     Sub(Box<Expr>, Box<Expr>, Range<PointerOffset<str>>),
     Paren(Box<Expr>, Range<PointerOffset<str>>),
     NegParen(Box<Expr>, Range<PointerOffset<str>>),
+    Neg(Box<Expr>, Range<PointerOffset<str>>),
+    Factorial(Box<Expr>, Range<PointerOffset<str>>),
+    Call(FnKind, Vec<Expr>, Range<PointerOffset<str>>),
+    /// A variable, with its value once bound by `Expr::bind`.
+    Var(String, Option<ValueTy>, Range<PointerOffset<str>>),
+}
+
+/// The values of the variables, e.g. `x` after `x = 1/3`, and `ans`.
+pub type Variables = HashMap<String, ValueTy>;
+
+/// A pass over `Expr`.
+///
+/// By default each node visits its operands from left to right, so a pass
+/// overrides only the kinds of the nodes it is interested in, calling `walk`
+/// to continue into the operands if needed.
+pub trait Visitor {
+    fn visit(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(lit, range) => self.visit_literal(lit, range),
+            Expr::Const(..) | Expr::Var(..) => {}
+            Expr::Mul(lhs, rhs, _)
+            | Expr::Div(lhs, rhs, _)
+            | Expr::Rem(lhs, rhs, _)
+            | Expr::Add(lhs, rhs, _)
+            | Expr::Sub(lhs, rhs, _) => self.visit_binary(expr, lhs, rhs),
+            Expr::Paren(inner, _)
+            | Expr::NegParen(inner, _)
+            | Expr::Neg(inner, _)
+            | Expr::Factorial(inner, _) => self.visit_unary(expr, inner),
+            Expr::Call(_, args, _) => self.visit_call(expr, args),
+        }
+    }
+
+    fn visit_literal(
+        &mut self,
+        _lit: &LitComponent,
+        _range: &Range<PointerOffset<str>>,
+    ) {
+    }
+
+    /// `Mul`, `Div`, `Rem`, `Add`, and `Sub`.
+    fn visit_binary(&mut self, expr: &Expr, _lhs: &Expr, _rhs: &Expr) {
+        walk(self, expr);
+    }
+
+    /// `Paren`, `NegParen`, `Neg`, and `Factorial`.
+    fn visit_unary(&mut self, expr: &Expr, _inner: &Expr) { walk(self, expr); }
+
+    fn visit_call(&mut self, expr: &Expr, _args: &[Expr]) { walk(self, expr); }
+}
+
+/// Visits the operands of `expr`.
+pub fn walk<V: Visitor + ?Sized>(v: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Literal(..) | Expr::Const(..) | Expr::Var(..) => {}
+        Expr::Mul(lhs, rhs, _)
+        | Expr::Div(lhs, rhs, _)
+        | Expr::Rem(lhs, rhs, _)
+        | Expr::Add(lhs, rhs, _)
+        | Expr::Sub(lhs, rhs, _) => {
+            v.visit(lhs);
+            v.visit(rhs);
+        }
+        Expr::Paren(inner, _)
+        | Expr::NegParen(inner, _)
+        | Expr::Neg(inner, _)
+        | Expr::Factorial(inner, _) => v.visit(inner),
+        Expr::Call(_, args, _) => args.iter().for_each(|x| v.visit(x)),
+    }
+}
+
+/// Counts the nodes of the expression.
+#[derive(Debug, Default)]
+pub struct NodeCounter {
+    pub count: usize,
+}
+
+impl Visitor for NodeCounter {
+    fn visit(&mut self, expr: &Expr) {
+        self.count += 1;
+        walk(self, expr);
+    }
 }
 
 pub type ValueTy = (BigRational, f64);
 
-#[derive(Debug)]
+pub type EvalResult = Result<(ValueTy, Range<usize>), EvalError>;
+
+/// `EvalResult` with the value computed in `f32` throughout.
+pub type EvalResultF32 = Result<(ValueTy, Range<usize>, f32), EvalError>;
+
+#[derive(Clone, Debug)]
 pub enum EvalError {
     ZeroDivision(Range<usize>),
+    DomainError(Range<usize>, &'static str),
+    /// Exceeded `EvalOptions::with_timeout` while evaluating the range.
+    Timeout(Range<usize>),
+    /// A variable neither assigned nor bound automatically.
+    Undefined(Range<usize>),
+    /// A literal whose digits the parser let through but cannot be read.
+    BadLiteral(Range<usize>),
+    /// Nested deeper than `EvalOptions::max_depth`, at the outermost
+    /// parenthesis beyond the limit.
+    TooDeep(Range<usize>),
 }
 
+/// Rejects `s` with the parentheses nested deeper than `opts.max_depth()`
+/// before parsing it, as the parser recurses on them as well. The range is
+/// of the outermost pair beyond the limit, up to the end if unclosed.
+pub fn check_depth(s: &str, opts: &EvalOptions) -> Result<(), EvalError> {
+    let mut open = vec![];
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => open.push(i),
+            ')' => {
+                open.pop();
+            }
+            _ => {}
+        }
+        if open.len() > opts.max_depth() {
+            let start = open[opts.max_depth()];
+            let mut level = 0;
+            let end = s[start..]
+                .char_indices()
+                .find(|&(_, c)| {
+                    level += match c {
+                        '(' => 1,
+                        ')' => -1,
+                        _ => 0,
+                    };
+                    level == 0
+                })
+                .map_or(s.len(), |(j, _)| start + j + 1);
+            return Err(EvalError::TooDeep(start..end));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WarningKind {
+    /// The float result is not finite although the operands are.
+    Overflow,
+    /// Not a problem but a note; the float result is exact although some of
+    /// the subexpressions are not, e.g. `(1/3) * 3`.
+    ExactDespiteInexact,
+    /// Also a note; the truth of the constant is a rational approximation.
+    ApproximateTruth(ConstKind),
+    /// Also a note; the truth of the square root is a rational
+    /// approximation, e.g. `sqrt(2)`.
+    IrrationalRoot,
+    /// `+` or `-` of nearly cancelling operands lost about this many
+    /// significant digits, e.g. `1.0001 - 1`.
+    Cancellation(i32),
+}
+
+impl WarningKind {
+    pub fn is_note(self) -> bool {
+        matches!(
+            self,
+            WarningKind::ExactDespiteInexact
+                | WarningKind::ApproximateTruth(_)
+                | WarningKind::IrrationalRoot
+        )
+    }
+}
+
+impl std::fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WarningKind::Overflow => write!(f, "overflow"),
+            WarningKind::ExactDespiteInexact => {
+                write!(f, "exact despite inexact intermediates")
+            }
+            WarningKind::ApproximateTruth(kind) => {
+                let name = kind.name();
+                write!(f, "{name} is irrational; its truth is approximate")
+            }
+            WarningKind::IrrationalRoot => {
+                write!(f, "the root is irrational; its truth is approximate")
+            }
+            WarningKind::Cancellation(digits) => {
+                write!(f, "loss of significance, ≈ {digits} digits cancelled")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub span: Range<usize>,
+    /// The operator at fault within `span`, e.g. `*` of `1e300 * 1e300`.
+    pub op: Option<Range<usize>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ExprTy {
     Literal,
+    /// `x + y` and the other binary operators, and also the factorial `n!`.
     Binary,
     Paren,
+    /// `-x`, while `-(x)` is `Paren`.
+    Neg,
+    Call,
 }
 
 pub struct EvalContext {
@@ -186,73 +909,592 @@ pub struct EvalContext {
     depth: usize,
 }
 
+impl EvalContext {
+    pub fn new(expr_ty: ExprTy, depth: usize) -> Self {
+        Self { expr_ty, depth }
+    }
+}
+
+/// The floored remainder, which has the sign of the divisor as the one on
+/// the rationals.
+fn rem_floored<F: Float>(a: F, b: F) -> F {
+    let r = a % b;
+    if r != F::zero() && (r < F::zero()) != (b < F::zero()) { r + b } else { r }
+}
+
+/// The factorial by the repeated multiplication; NaN unless `x` is a
+/// non-negative integer.
+fn factorial_float<F: Float>(x: F) -> F {
+    if x < F::zero() || x.fract() != F::zero() {
+        return F::nan();
+    }
+    let (mut res, mut i) = (F::one(), F::one());
+    while i <= x && res.is_finite() {
+        res = res * i;
+        i = i + F::one();
+    }
+    res
+}
+
+// The rational arithmetic with an exact zero operand is short-circuited.
+// The float side is always computed as is, so that `0 * inf` is still NaN.
+
+fn mul_rat(lhs: BigRational, rhs: BigRational) -> BigRational {
+    if lhs.is_zero() || rhs.is_zero() { BigRational::zero() } else { lhs * rhs }
+}
+
+fn add_rat(lhs: BigRational, rhs: BigRational) -> BigRational {
+    match (lhs.is_zero(), rhs.is_zero()) {
+        (true, _) => rhs,
+        (_, true) => lhs,
+        _ => lhs + rhs,
+    }
+}
+
+/// `min` and `max` compare the truths, and take the float of the argument
+/// chosen, the first one among the ties.
+fn eval_call(
+    kind: FnKind,
+    vals: Vec<ValueTy>,
+    range: &Range<usize>,
+    warnings: &mut Vec<Warning>,
+) -> Result<ValueTy, EvalError> {
+    let mut it = vals.into_iter();
+    let (rat, flt) = it.next().unwrap();
+    let val = match kind {
+        FnKind::Sqrt => {
+            if rat.is_negative() {
+                return Err(EvalError::DomainError(
+                    range.clone(),
+                    "square root of a non-negative number only",
+                ));
+            }
+            let rat = sqrt_rat(&rat).unwrap_or_else(|| {
+                let span = range.clone();
+                let kind = WarningKind::IrrationalRoot;
+                warnings.push(Warning { kind, span, op: None });
+                sqrt_approx(&rat)
+            });
+            (rat, flt.sqrt())
+        }
+        FnKind::Abs => (rat.abs(), flt.abs()),
+        FnKind::Min => {
+            it.fold((rat, flt), |x, y| if y.0 < x.0 { y } else { x })
+        }
+        FnKind::Max => {
+            it.fold((rat, flt), |x, y| if y.0 > x.0 { y } else { x })
+        }
+        FnKind::Gcd | FnKind::Lcm => {
+            let (rhs, rhs_flt) = it.next().unwrap();
+            if !rat.is_integer() || !rhs.is_integer() {
+                return Err(EvalError::DomainError(
+                    range.clone(),
+                    "gcd and lcm of integers only",
+                ));
+            }
+            let (lhs, rhs) = (rat.numer(), rhs.numer());
+            if kind == FnKind::Gcd {
+                (BigRational::from(lhs.gcd(rhs)), gcd_float(flt, rhs_flt))
+            } else {
+                (BigRational::from(lhs.lcm(rhs)), lcm_float(flt, rhs_flt))
+            }
+        }
+    };
+    Ok(val)
+}
+
+/// `eval_call` on the `f32` values, where `min` and `max` compare them
+/// rather than the truths.
+fn eval_call_f32(kind: FnKind, args: Vec<f32>) -> f32 {
+    let mut it = args.into_iter();
+    let first = it.next().unwrap();
+    match kind {
+        FnKind::Sqrt => first.sqrt(),
+        FnKind::Abs => first.abs(),
+        FnKind::Min => it.fold(first, f32::min),
+        FnKind::Max => it.fold(first, f32::max),
+        FnKind::Gcd => gcd_float(first, it.next().unwrap()),
+        FnKind::Lcm => lcm_float(first, it.next().unwrap()),
+    }
+}
+
+/// The Euclidean algorithm on floats, exact for the integers; NaN unless
+/// both are integers.
+fn gcd_float<F: Float>(a: F, b: F) -> F {
+    if a.fract() != F::zero() || b.fract() != F::zero() {
+        return F::nan();
+    }
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != F::zero() {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn lcm_float<F: Float>(a: F, b: F) -> F {
+    let g = gcd_float(a, b);
+    if g == F::zero() { g } else { (a / g * b).abs() }
+}
+
+/// The exact square root, if both the numerator and the denominator are
+/// perfect squares.
+fn sqrt_rat(rat: &BigRational) -> Option<BigRational> {
+    let root = |n: &BigInt| Some(n.sqrt()).filter(|r| &(r * r) == n);
+    Some(BigRational::new(root(rat.numer())?, root(rat.denom())?))
+}
+
+/// A square root kept symbolic, `coef * sqrt(radicand)`, so that the
+/// products and the quotients of the same radical are exact, e.g.
+/// `sqrt(2) * sqrt(2)` or `sqrt(8) / sqrt(2)`. The sums are not; they fall
+/// back to the approximate truths.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Radical {
+    coef: BigRational,
+    /// Not a square of a rational, unless it is `1`.
+    radicand: BigRational,
+}
+
+/// The value with its range, the radical if symbolic, and the value
+/// computed in `f32` throughout with the same operations as the `f64` one.
+type Evaluated = (ValueTy, Range<usize>, Option<Box<Radical>>, f32);
+
+impl Radical {
+    /// `coef * sqrt(radicand)`, with the root taken out if it is rational.
+    fn new(coef: BigRational, radicand: BigRational) -> Self {
+        match sqrt_rat(&radicand) {
+            Some(root) => {
+                Self { coef: coef * root, radicand: BigRational::one() }
+            }
+            None => Self { coef, radicand },
+        }
+    }
+
+    /// The evaluated operand as a radical, `x * sqrt(1)` if not symbolic.
+    fn of((val, _, rad, _): &Evaluated) -> Self {
+        rad.as_deref().cloned().unwrap_or_else(|| Self {
+            coef: val.0.clone(),
+            radicand: BigRational::one(),
+        })
+    }
+
+    /// The square root of the truth, unless it is negative.
+    fn sqrt(rat: &BigRational) -> Option<Box<Self>> {
+        let one = BigRational::one();
+        (!rat.is_negative()).then(|| Box::new(Self::new(one, rat.to_owned())))
+    }
+
+    /// The product, or the quotient if `div`, unless neither operand is
+    /// symbolic. The divisor is not zero.
+    fn mul(lhs: &Evaluated, rhs: &Evaluated, div: bool) -> Option<Box<Self>> {
+        if lhs.2.is_none() && rhs.2.is_none() {
+            return None;
+        }
+        let (l, r) = (Self::of(lhs), Self::of(rhs));
+        Some(Box::new(if div {
+            Self::new(l.coef / r.coef, l.radicand / r.radicand)
+        } else {
+            Self::new(l.coef * r.coef, l.radicand * r.radicand)
+        }))
+    }
+
+    /// The value with the truth of `radical` if any, which stays symbolic
+    /// unless it turns out rational, e.g. `sqrt(2) * sqrt(2)`.
+    fn resolve(
+        val: ValueTy,
+        radical: Option<Box<Self>>,
+    ) -> (ValueTy, Option<Box<Self>>) {
+        match radical {
+            Some(rad) if rad.radicand.is_one() => ((rad.coef, val.1), None),
+            Some(rad) => {
+                let truth =
+                    mul_rat(rad.coef.clone(), sqrt_approx(&rad.radicand));
+                ((truth, val.1), Some(rad))
+            }
+            None => (val, None),
+        }
+    }
+}
+
+/// The square root truncated to 50 decimal places, for the irrational ones.
+fn sqrt_approx(rat: &BigRational) -> BigRational {
+    let scale = BigInt::from(10).pow(50);
+    let (num, den) = (rat.numer(), rat.denom());
+    BigRational::new((num * &scale * &scale / den).sqrt(), scale)
+}
+
+/// Warns of the cancellation in `res`, the sum or difference of `lhs` and
+/// `rhs`, when it is smaller than the larger operand by `CANCEL_DIGITS`
+/// digits or more. The exact zero has no digits to lose.
+fn check_cancellation(
+    (lhs, rhs, res): (&BigRational, &BigRational, &BigRational),
+    span: &Range<usize>,
+    warnings: &mut Vec<Warning>,
+) {
+    if res.is_zero() {
+        return;
+    }
+    let larger = lhs.abs().max(rhs.abs());
+    let digits = ilog10(&(larger / res.abs()));
+    if digits >= CANCEL_DIGITS {
+        let kind = WarningKind::Cancellation(digits);
+        warnings.push(Warning { kind, span: span.clone(), op: None });
+    }
+}
+
+fn sub_rat(lhs: BigRational, rhs: BigRational) -> BigRational {
+    match (lhs.is_zero(), rhs.is_zero()) {
+        (_, true) => lhs,
+        (true, _) => -rhs,
+        _ => lhs - rhs,
+    }
+}
+
 impl Expr {
-    pub fn eval(
+    fn range(&self) -> &Range<PointerOffset<str>> {
+        match self {
+            Expr::Literal(_, range)
+            | Expr::Const(_, range)
+            | Expr::Mul(_, _, range)
+            | Expr::Div(_, _, range)
+            | Expr::Rem(_, _, range)
+            | Expr::Add(_, _, range)
+            | Expr::Sub(_, _, range)
+            | Expr::Paren(_, range)
+            | Expr::NegParen(_, range)
+            | Expr::Neg(_, range)
+            | Expr::Factorial(_, range)
+            | Expr::Call(_, _, range)
+            | Expr::Var(_, _, range) => range,
+        }
+    }
+
+    /// The kind of the node, which tells the `estimate=` flag it is
+    /// estimated under.
+    pub fn expr_ty(&self) -> ExprTy {
+        match self {
+            Expr::Literal(..) | Expr::Const(..) | Expr::Var(..) => {
+                ExprTy::Literal
+            }
+            Expr::Add(..)
+            | Expr::Sub(..)
+            | Expr::Mul(..)
+            | Expr::Div(..)
+            | Expr::Rem(..)
+            | Expr::Factorial(..) => ExprTy::Binary,
+            Expr::Paren(..) | Expr::NegParen(..) => ExprTy::Paren,
+            Expr::Neg(..) => ExprTy::Neg,
+            Expr::Call(..) => ExprTy::Call,
+        }
+    }
+
+    /// Byte ranges of the literals, in the order of appearance.
+    pub fn literal_ranges(&self, s: &str) -> Vec<Range<usize>> {
+        struct Ranges<'a>(&'a str, Vec<Range<usize>>);
+        impl Visitor for Ranges<'_> {
+            fn visit_literal(
+                &mut self,
+                _: &LitComponent,
+                range: &Range<PointerOffset<str>>,
+            ) {
+                self.1.push(translate_range(range, self.0));
+            }
+        }
+
+        let mut ranges = Ranges(s, vec![]);
+        ranges.visit(self);
+        ranges.1
+    }
+
+    /// Gives the variables their values in `vars`. The ones not in `vars`
+    /// are left unbound, which are errors on evaluation.
+    pub fn bind(&mut self, vars: &Variables) {
+        match self {
+            Expr::Var(name, val, _) => *val = vars.get(name).cloned(),
+            Expr::Literal(..) | Expr::Const(..) => {}
+            Expr::Mul(lhs, rhs, _)
+            | Expr::Div(lhs, rhs, _)
+            | Expr::Rem(lhs, rhs, _)
+            | Expr::Add(lhs, rhs, _)
+            | Expr::Sub(lhs, rhs, _) => {
+                lhs.bind(vars);
+                rhs.bind(vars);
+            }
+            Expr::Paren(inner, _)
+            | Expr::NegParen(inner, _)
+            | Expr::Neg(inner, _)
+            | Expr::Factorial(inner, _) => inner.bind(vars),
+            Expr::Call(_, args, _) => {
+                args.iter_mut().for_each(|x| x.bind(vars))
+            }
+        }
+    }
+
+    pub fn eval(self, s: &str, opts: &EvalOptions, depth: usize) -> EvalResult {
+        self.eval_(s, &opts.started(), depth, &mut vec![], &mut false)
+            .map(|(val, range, ..)| (val, range))
+    }
+
+    /// Same as `eval`, but also returns the warnings found so far, in the
+    /// order of evaluation.
+    pub fn eval_with_warnings(
+        self,
+        s: &str,
+        opts: &EvalOptions,
+    ) -> (EvalResult, Vec<Warning>) {
+        let (res, warnings) = self.eval_with_f32(s, opts);
+        (res.map(|(val, range, _)| (val, range)), warnings)
+    }
+
+    /// Same as `eval_with_warnings`, but also returns the value computed in
+    /// `f32` throughout. Divisions by zero yield infinities or NaN there.
+    pub fn eval_with_f32(
+        self,
+        s: &str,
+        opts: &EvalOptions,
+    ) -> (EvalResultF32, Vec<Warning>) {
+        let mut warnings = vec![];
+        let res = self.eval_(s, &opts.started(), 0, &mut warnings, &mut false);
+        (res.map(|(val, range, _, flt32)| (val, range, flt32)), warnings)
+    }
+
+    fn eval_(
+        self,
+        s: &str,
+        opts: &EvalOptions,
+        depth: usize,
+        warnings: &mut Vec<Warning>,
+        inexact: &mut bool,
+    ) -> Result<Evaluated, EvalError> {
+        // `1 + 2 + ... + 9` nests to the left as deep as it is long, so its
+        // left operands are walked down in a loop rather than by recursion,
+        // leaving only the parentheses, calls and unary minuses to nest.
+        let mut spine = vec![];
+        let mut expr = self;
+        while let Expr::Mul(lhs, ..)
+        | Expr::Div(lhs, ..)
+        | Expr::Rem(lhs, ..)
+        | Expr::Add(lhs, ..)
+        | Expr::Sub(lhs, ..)
+        | Expr::Factorial(lhs, _) = &mut expr
+        {
+            let hole = Expr::Var(String::new(), None, lhs.range().clone());
+            let lhs = std::mem::replace(lhs.as_mut(), hole);
+            spine.push(expr);
+            expr = lhs;
+        }
+
+        let depth = depth + spine.len();
+        let mut acc =
+            expr.eval_node(s, opts, depth, warnings, inexact, None)?;
+        for (i, expr) in spine.into_iter().rev().enumerate() {
+            let depth = depth - 1 - i;
+            let lhs = Some(acc);
+            acc = expr.eval_node(s, opts, depth, warnings, inexact, lhs)?;
+        }
+        Ok(acc)
+    }
+
+    /// Evaluates the node, with `lhs` as the value of its left operand, or
+    /// of the operand of `!`, if already evaluated.
+    fn eval_node(
         self,
         s: &str,
         opts: &EvalOptions,
         depth: usize,
-    ) -> Result<(ValueTy, Range<usize>), EvalError> {
-        let ctx = EvalContext {
-            expr_ty: match self {
-                Expr::Literal(..) => ExprTy::Literal,
-                Expr::Add(..)
-                | Expr::Sub(..)
-                | Expr::Mul(..)
-                | Expr::Div(..) => ExprTy::Binary,
-                Expr::Paren(..) | Expr::NegParen(..) => ExprTy::Paren,
-            },
-            depth,
+        warnings: &mut Vec<Warning>,
+        inexact: &mut bool,
+        mut lhs: Option<Evaluated>,
+    ) -> Result<Evaluated, EvalError> {
+        let ctx = EvalContext::new(self.expr_ty(), depth);
+        let mut left = |expr: Box<Expr>, warnings: &mut _, inexact: &mut _| {
+            let eval = || expr.eval_(s, opts, depth + 1, warnings, inexact);
+            lhs.take().map_or_else(eval, Ok)
+        };
+
+        // the operator, singled out in the overflow warning
+        let op = match &self {
+            Expr::Mul(_, _, op)
+            | Expr::Div(_, _, op)
+            | Expr::Rem(_, _, op)
+            | Expr::Add(_, _, op)
+            | Expr::Sub(_, _, op)
+            | Expr::Factorial(_, op) => Some(translate_range(op, s)),
+            _ => None,
         };
 
-        let (val, range) = match self {
+        // float values of the operands, to detect overflow
+        let mut args = vec![];
+        // the value kept symbolic, which replaces the truth
+        let mut radical = None;
+        let (val, range, flt32) = match self {
             Expr::Literal(lit, range) => {
-                let start = range.start.translate_position(s);
-                let end = range.end.translate_position(s);
-                (lit.eval(), start..end)
+                let range = translate_range(&range, s);
+                (lit.eval(range.clone())?, range, lit.eval_f32())
+            }
+            Expr::Const(kind, range) => {
+                let span = translate_range(&range, s);
+                let note = WarningKind::ApproximateTruth(kind);
+                let warning =
+                    Warning { kind: note, span: span.clone(), op: None };
+                warnings.push(warning);
+                (kind.eval(), span, kind.eval_f32())
+            }
+            Expr::Var(_, val, range) => {
+                let range = translate_range(&range, s);
+                match val {
+                    Some(val) => {
+                        let flt32 = val.1 as f32;
+                        (val, range, flt32)
+                    }
+                    None => return Err(EvalError::Undefined(range)),
+                }
             }
             Expr::Mul(lhs, rhs, _) => {
-                let lhs = lhs.eval(s, opts, depth + 1)?;
-                let rhs = rhs.eval(s, opts, depth + 1)?;
+                let lhs = left(lhs, warnings, inexact)?;
+                let rhs = rhs.eval_(s, opts, depth + 1, warnings, inexact)?;
                 let range = lhs.1.start..rhs.1.end;
-                ((lhs.0.0 * rhs.0.0, lhs.0.1 * rhs.0.1), range)
+                args = vec![lhs.0.1, rhs.0.1];
+                radical = Radical::mul(&lhs, &rhs, false);
+                let flt32 = lhs.3 * rhs.3;
+                ((mul_rat(lhs.0.0, rhs.0.0), lhs.0.1 * rhs.0.1), range, flt32)
             }
             Expr::Div(lhs, rhs, _) => {
-                let lhs = lhs.eval(s, opts, depth + 1)?;
-                let rhs = rhs.eval(s, opts, depth + 1)?;
+                let lhs = left(lhs, warnings, inexact)?;
+                let rhs = rhs.eval_(s, opts, depth + 1, warnings, inexact)?;
+                let range = lhs.1.start..rhs.1.end;
+                args = vec![lhs.0.1, rhs.0.1];
+                if rhs.0.0.is_zero() {
+                    return Err(EvalError::ZeroDivision(range));
+                }
+                radical = Radical::mul(&lhs, &rhs, true);
+                let flt32 = lhs.3 / rhs.3;
+                ((lhs.0.0 / rhs.0.0, lhs.0.1 / rhs.0.1), range, flt32)
+            }
+            Expr::Rem(lhs, rhs, _) => {
+                let lhs = left(lhs, warnings, inexact)?;
+                let rhs = rhs.eval_(s, opts, depth + 1, warnings, inexact)?;
                 let range = lhs.1.start..rhs.1.end;
+                args = vec![lhs.0.1, rhs.0.1];
                 if rhs.0.0.is_zero() {
                     return Err(EvalError::ZeroDivision(range));
                 }
-                ((lhs.0.0 / rhs.0.0, lhs.0.1 / rhs.0.1), range)
+                let rat = &lhs.0.0 - &rhs.0.0 * (&lhs.0.0 / &rhs.0.0).floor();
+                let flt32 = rem_floored(lhs.3, rhs.3);
+                ((rat, rem_floored(lhs.0.1, rhs.0.1)), range, flt32)
             }
             Expr::Add(lhs, rhs, _) => {
-                let lhs = lhs.eval(s, opts, depth + 1)?;
-                let rhs = rhs.eval(s, opts, depth + 1)?;
+                let lhs = left(lhs, warnings, inexact)?;
+                let rhs = rhs.eval_(s, opts, depth + 1, warnings, inexact)?;
                 let range = lhs.1.start..rhs.1.end;
-                ((lhs.0.0 + rhs.0.0, lhs.0.1 + rhs.0.1), range)
+                args = vec![lhs.0.1, rhs.0.1];
+                if opts.show_cancel() {
+                    let res = &lhs.0.0 + &rhs.0.0;
+                    let ops = (&lhs.0.0, &rhs.0.0, &res);
+                    check_cancellation(ops, &range, warnings);
+                }
+                let flt32 = lhs.3 + rhs.3;
+                ((add_rat(lhs.0.0, rhs.0.0), lhs.0.1 + rhs.0.1), range, flt32)
             }
             Expr::Sub(lhs, rhs, _) => {
-                let lhs = lhs.eval(s, opts, depth + 1)?;
-                let rhs = rhs.eval(s, opts, depth + 1)?;
+                let lhs = left(lhs, warnings, inexact)?;
+                let rhs = rhs.eval_(s, opts, depth + 1, warnings, inexact)?;
                 let range = lhs.1.start..rhs.1.end;
-                ((lhs.0.0 - rhs.0.0, lhs.0.1 - rhs.0.1), range)
+                args = vec![lhs.0.1, rhs.0.1];
+                if opts.show_cancel() {
+                    let res = &lhs.0.0 - &rhs.0.0;
+                    let ops = (&lhs.0.0, &rhs.0.0, &res);
+                    check_cancellation(ops, &range, warnings);
+                }
+                let flt32 = lhs.3 - rhs.3;
+                ((sub_rat(lhs.0.0, rhs.0.0), lhs.0.1 - rhs.0.1), range, flt32)
             }
             Expr::Paren(inner, range) => {
-                let inner = inner.eval(s, opts, depth + 1)?;
-                let start = range.start.translate_position(s);
-                let end = range.end.translate_position(s);
-                (inner.0, start..end)
+                let inner =
+                    inner.eval_(s, opts, depth + 1, warnings, inexact)?;
+                radical = inner.2;
+                (inner.0, translate_range(&range, s), inner.3)
+            }
+            Expr::Factorial(inner, range) => {
+                let inner = left(inner, warnings, inexact)?;
+                let range = inner.1.start..translate_range(&range, s).end;
+                let (rat, flt) = inner.0;
+                args = vec![flt];
+                if !rat.is_integer() || rat.is_negative() {
+                    return Err(EvalError::DomainError(
+                        range,
+                        "factorial of a non-negative integer only",
+                    ));
+                }
+                if rat.to_integer() > MAX_FACTORIAL.into() {
+                    let msg = MAX_FACTORIAL_ERROR;
+                    return Err(EvalError::DomainError(range, msg));
+                }
+                let mut prod = BigInt::one();
+                let it = num::range_inclusive(BigInt::one(), rat.to_integer());
+                for (i, k) in it.enumerate() {
+                    if i % 64 == 0 && opts.timed_out() {
+                        return Err(EvalError::Timeout(range));
+                    }
+                    prod *= k;
+                }
+                let prod = BigRational::from_integer(prod);
+                ((prod, factorial_float(flt)), range, factorial_float(inner.3))
             }
-            Expr::NegParen(inner, range) => {
-                let inner = inner.eval(s, opts, depth + 1)?;
-                let start = range.start.translate_position(s);
-                let end = range.end.translate_position(s);
-                ((-inner.0.0, -inner.0.1), start..end)
+            Expr::Call(kind, operands, range) => {
+                let (mut vals, mut vals32) = (vec![], vec![]);
+                let mut symbolic = false;
+                for x in operands {
+                    let x = x.eval_(s, opts, depth + 1, warnings, inexact)?;
+                    symbolic |= x.2.is_some();
+                    vals.push(x.0);
+                    vals32.push(x.3);
+                }
+                let range = translate_range(&range, s);
+                // the root of a root is left approximate
+                if kind == FnKind::Sqrt && !symbolic {
+                    radical = Radical::sqrt(&vals[0].0);
+                }
+                args = vals.iter().map(|x| x.1).collect();
+                let val = eval_call(kind, vals, &range, warnings)?;
+                (val, range, eval_call_f32(kind, vals32))
+            }
+            Expr::NegParen(inner, range) | Expr::Neg(inner, range) => {
+                let inner =
+                    inner.eval_(s, opts, depth + 1, warnings, inexact)?;
+                radical = inner.2.map(|rad| {
+                    Box::new(Radical { coef: -rad.coef.clone(), ..*rad })
+                });
+                let range = translate_range(&range, s);
+                ((-inner.0.0, -inner.0.1), range, -inner.3)
             }
         };
 
-        estimate(&val, range.clone(), s, opts, &ctx);
-        Ok((val, range))
+        if opts.timed_out() {
+            return Err(EvalError::Timeout(range));
+        }
+
+        let (val, radical) = Radical::resolve(val, radical);
+
+        if !args.is_empty()
+            && args.iter().all(|x| x.is_finite())
+            && !val.1.is_finite()
+        {
+            let span = range.clone();
+            let kind = WarningKind::Overflow;
+            warnings.push(Warning { kind, span, op });
+        }
+
+        // `inexact` tells whether any of the subexpressions was inexact
+        let exact = BigRational::from_float(val.1).as_ref() == Some(&val.0);
+        if depth == 0 && exact && *inexact {
+            let span = range.clone();
+            let kind = WarningKind::ExactDespiteInexact;
+            warnings.push(Warning { kind, span, op: None });
+        }
+        *inexact |= !exact;
+
+        let shown32 = (opts.precision() == Precision::F32).then_some(flt32);
+        estimate(&val, shown32, range.clone(), s, opts, &ctx);
+        Ok((val, range, radical, flt32))
     }
 }
@@ -1,10 +1,58 @@
 use yansi::Color;
 
+/// The default colors of `ui::Palette`.
 pub const EMPH_COLOR: Color = Color::Fixed(201);
 pub const AUX_COLOR: Color = Color::Fixed(3);
 pub const DARK_COLOR: Color = Color::Fixed(246);
 pub const ERR_COLOR: Color = Color::Fixed(9);
 pub const OK_COLOR: Color = Color::Fixed(10);
 
+/// The truths of `pi` and `e`, truncated far beyond the precision of `f64`.
+pub const PI_DIGITS: &str =
+    "3.141592653589793238462643383279502884197169399375105820974944";
+pub const E_DIGITS: &str =
+    "2.718281828459045235360287471352662497757247093699959574966967";
+
+/// Width of the reports when the terminal width is unknown.
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Longest period of the truth written out, unless set otherwise.
+pub const DEFAULT_MAX_PERIOD: usize = 100_000;
+
+/// Fractional digits written by `{:#}` of `DecimalTuple` unless a
+/// precision is given.
+pub const ALTERNATE_DIGITS: usize = 16;
+
+/// Largest exponent of `DecimalTuple`, in magnitude, beyond which the
+/// digits would take too much memory, e.g. `1e2000000000`.
+pub const MAX_EXPONENT: u32 = 100_000;
+
+/// Most fractional digits of `--digits` and `--show-rounding`, beyond
+/// which the power of ten would take too long and too much memory.
+pub const MAX_DIGITS: usize = 100_000;
+
+/// Deepest nesting of the parentheses, calls and unary minuses, unless set
+/// otherwise.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Stack of the thread parsing and evaluating the input, on which the
+/// nesting up to `DEFAULT_MAX_DEPTH` fits even in debug builds.
+pub const STACK_SIZE: usize = 64 << 20;
+
+/// Largest argument of the factorial, beyond which the product would take
+/// too long and too much memory, e.g. `100000000!`.
+pub const MAX_FACTORIAL: u32 = 10_000;
+/// The error of a factorial beyond `MAX_FACTORIAL`, which is checked to
+/// agree with it.
+pub const MAX_FACTORIAL_ERROR: &str =
+    "factorial of an integer up to 10000 only";
+
+/// Most assignments that `:undo` can revert, beyond which the oldest are
+/// forgotten.
+pub const MAX_UNDO: usize = 1000;
+
+/// Fewest significant digits lost for `+cancel` to warn of, e.g. `1.001 - 1`.
+pub const CANCEL_DIGITS: i32 = 3;
+
 pub const VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
 pub const PROLOGUE: &str = r#"Welcome to feather REPL. Type ":help" for help."#;
@@ -3,5 +3,165 @@ pub mod constants;
 pub mod number;
 pub mod parser;
 pub mod repl;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 pub mod ui;
 pub mod utils;
+
+use combine::easy::Errors;
+
+use crate::{
+    ast::{check_depth, EvalError, EvalOptions, ValueTy, Variables},
+    parser::{parse_line_checked, parse_program_checked},
+    ui::{
+        capture_estimates, str_backmatter, str_error_report, str_frontmatter,
+    },
+    utils::{display_width, with_stack},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    /// Errors from the parser, positioned at byte offsets of the input.
+    Parse(Errors<char, String, usize>),
+    Eval(EvalError),
+}
+
+/// Parses and evaluates `input` without printing anything.
+///
+/// It keeps no state of its own, and `EvalOptions`, `Expr`, and the errors
+/// are `Send + Sync`, so it can be called from several threads at once.
+pub fn evaluate(input: &str, opts: &EvalOptions) -> Result<ValueTy, Error> {
+    with_stack(|| evaluate_(input, opts))
+}
+
+fn evaluate_(input: &str, opts: &EvalOptions) -> Result<ValueTy, Error> {
+    check_depth(input, opts).map_err(Error::Eval)?;
+    let expr =
+        parse_line_checked(input, opts.strict_literals(), opts.max_depth())
+            .map_err(Error::Parse)?;
+    let (val, _) =
+        expr.eval(input, &opts.silenced(), 0).map_err(Error::Eval)?;
+    Ok(val)
+}
+
+/// The widest line, in display columns, of the report that the REPL
+/// prints for `input` in the pretty format, without printing it. Each of
+/// the `;`-separated statements is evaluated with the variables assigned
+/// before it, as in the REPL, and the estimates of the subexpressions are
+/// counted as `opts` selects them.
+pub fn report_width(input: &str, opts: &EvalOptions) -> usize {
+    with_stack(|| report_width_(input, opts))
+}
+
+fn report_width_(input: &str, opts: &EvalOptions) -> usize {
+    let width = |report: String| report.lines().map(display_width).max();
+    if let Err(e) = check_depth(input, opts) {
+        let report =
+            str_frontmatter("stdin", 1) + &str_backmatter(input, &Err(e), &[]);
+        return width(report).unwrap_or(0);
+    }
+    let mut vars = Variables::new();
+    let stmts =
+        parse_program_checked(input, opts.strict_literals(), opts.max_depth());
+    let widths = stmts.into_iter().map(|stmt| {
+        let mut report = str_frontmatter("stdin", 1);
+        match stmt {
+            Ok((name, mut expr)) => {
+                expr.bind(&vars);
+                let ((res, warnings), estimates) =
+                    capture_estimates(|| expr.eval_with_warnings(input, opts));
+                report += &estimates;
+                if let Ok((val, _)) = &res {
+                    let name = name.unwrap_or_else(|| "ans".to_owned());
+                    vars.insert(name, val.clone());
+                }
+                report += &str_backmatter(input, &res, &warnings);
+            }
+            Err(e) => report += &str_error_report(&e, input),
+        }
+        width(report)
+    });
+    widths.max().flatten().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let opts = EvalOptions::new();
+        let actual = evaluate("1/3 + 1/6", &opts).unwrap();
+        assert_eq!(actual, ("1/2".parse().unwrap(), 0.5));
+
+        let actual = evaluate("1 + * 2", &opts);
+        assert!(matches!(actual, Err(Error::Parse(e)) if e.position == 4));
+
+        let actual = evaluate("1 / (1 - 1)", &opts);
+        assert!(matches!(
+            actual,
+            Err(Error::Eval(EvalError::ZeroDivision(r))) if r == (0..11)
+        ));
+    }
+
+    #[test]
+    fn test_thread_safety() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<EvalOptions>();
+        assert_send_sync::<crate::ast::Expr>();
+        assert_send_sync::<Error>();
+
+        let test_suite = [
+            ("1/3 + 1/6", "1/2"),
+            ("0.1 + 0.2", "3/10"),
+            ("10!", "3628800"),
+            ("0x10 * 2", "32"),
+            ("(1/3 + 1/3 + 1/3) * 9", "9"),
+            ("1e-5", "1/100000"),
+        ];
+        let handles: Vec<_> = test_suite
+            .into_iter()
+            .map(|(s, expected)| {
+                std::thread::spawn(move || {
+                    let opts = EvalOptions::new();
+                    for _ in 0..10 {
+                        let (rat, _) = evaluate(s, &opts).unwrap();
+                        assert_eq!(rat, expected.parse().unwrap(), "{s}");
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_report_width() {
+        let opts = EvalOptions::new();
+        // ` │ float: 0.333333333333333314829616256247390992939472198486328125`
+        assert_eq!(report_width("1/3", &opts), 3 + 7 + 56);
+        // ` │  ╰── {this:?}: 0.5`, longer than ` │ float: 0.5`
+        assert_eq!(report_width("1/2", &opts), 3 + 18);
+        // ` │  *  Unexpected end of input`, longer than the caret lines
+        assert_eq!(report_width("1 + 23 *", &opts), 3 + 27);
+
+        // the statements, each in its own panel, with the variables bound
+        let width = report_width("1/3", &opts);
+        assert_eq!(report_width("x = 1/3", &opts), width);
+        assert_eq!(report_width("1/2; 1/3", &opts), width);
+        assert_eq!(report_width("x = 1/3; x * 1", &opts), width);
+        assert_eq!(report_width("1/3; ans * 1", &opts), width);
+        // the subexpressions estimated, e.g. `1/3` wider than the product
+        let opts_bin = EvalOptions::new().with_estimate(vec!["+bin".into()]);
+        assert!(report_width("1/3 * 0", &opts) < width);
+        assert_eq!(report_width("1/3 * 0", &opts_bin), width);
+        let opts_depth = opts_bin.with_estimate_depth(Some(1));
+        assert!(report_width("1/3 * 0 * 0", &opts_depth) < width);
+        // no `undefined variable` panel once `x` is assigned
+        assert!(
+            report_width("x = 1/2; x * 1", &opts)
+                < report_width("y = 1/2; x * 1", &opts)
+        );
+    }
+}
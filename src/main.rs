@@ -1,19 +1,352 @@
-use clap::Parser;
-use rustyline::Result;
+use std::{path::PathBuf, time::Duration};
 
-use feather_repl::repl::{repl, ReplOptions};
+use clap::{Parser, ValueEnum};
+use num::Signed;
+use num_rational::BigRational;
+use rustyline::{config::EditMode, Result};
+
+use feather_repl::{
+    ast::{ErrorUnit, OutputFormat, Precision},
+    constants::MAX_DIGITS,
+    number::DecimalTuple,
+    repl::{
+        benchmark_once, dump_history, eval_once, list_functions, repl,
+        run_batch, ReplOptions,
+    },
+    ui::{color_test, set_palette, terminal_width, Palette},
+};
 
 #[derive(Parser, Debug)]
 #[command(version)]
 pub struct Args {
     /// Which subexpressions to estimate.
     /// Valid values: "+lit" for literals, "+par" for parentheses,
-    /// "+bin" for binary operations, or the comma-separated value of these.
+    /// "+bin" for binary operations and factorials, "+neg" for negations
+    /// without parentheses, "+call" for function calls, or the
+    /// comma-separated value of these.
+    /// "+cf" additionally shows the continued fraction of the truth, "+ulp"
+    /// the floats right below and above it, "+hex" the float in the
+    /// hexadecimal form, "+err" the error in ppm and correct digits,
+    /// "+mixed" the truth as a mixed number, "+period" the length of the
+    /// repeating digits of the truth, "+cancel" a warning of the digits lost
+    /// by cancellation in additions and subtractions, "+group" the integer
+    /// digits of the truth in groups of three, e.g. "1,000,000", and "+sci"
+    /// a very large or small truth in the scientific notation, e.g. "1e-12".
     #[arg(short, long)]
     estimate: Vec<String>,
+
+    /// Estimate only the subexpressions at most this deep, the whole
+    /// expression being at depth 0.
+    #[arg(long, value_name = "N")]
+    estimate_depth: Option<usize>,
+
+    /// Also show the truth truncated and rounded to this many fractional
+    /// digits, at most 100000.
+    #[arg(
+        long,
+        value_name = "PLACES",
+        value_parser = digits_parser()
+    )]
+    show_rounding: Option<usize>,
+
+    /// Abbreviate continued fractions after this many terms.
+    #[arg(long, value_name = "N")]
+    max_terms: Option<usize>,
+
+    /// Width of the reports. Defaults to the terminal width, or 80 if it
+    /// is unknown.
+    #[arg(long, value_name = "COLUMNS")]
+    max_width: Option<usize>,
+
+    /// Reject literals with redundant leading zeros, e.g. "007".
+    #[arg(long)]
+    strict_literals: bool,
+
+    /// Follow an improper truth with the mixed number, e.g. "= 3 1/2".
+    #[arg(long)]
+    mixed_fractions: bool,
+
+    /// Collapse long runs of a digit in the truth, e.g. "0.1(0×8)1".
+    #[arg(long)]
+    group_repeats: bool,
+
+    /// Separator of the digit groups of "+group".
+    #[arg(long, value_name = "CHAR", default_value_t = ',')]
+    group_separator: char,
+
+    /// Also show the float exactly as "m * 2^e", e.g. "1 * 2^-1" for 0.5.
+    #[arg(long)]
+    dyadic: bool,
+
+    /// Write the negative zero float as "0" rather than "-0".
+    #[arg(long)]
+    compact_zero: bool,
+
+    /// Also show the relative error in this unit.
+    #[arg(long, value_enum)]
+    error_unit: Option<Unit>,
+
+    /// Floating-point type evaluated along with f64.
+    #[arg(long, value_enum, default_value_t = FloatTy::F64)]
+    precision: FloatTy,
+
+    /// Abort an evaluation running longer than this many milliseconds.
+    #[arg(long, value_name = "MS")]
+    timeout: Option<u64>,
+
+    /// Reject the parentheses, calls and unary minuses nested deeper than
+    /// this, rather than overflowing the stack.
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Print the time spent in parsing and evaluating after each panel,
+    /// excluding the time spent in printing the estimates.
+    #[arg(long)]
+    timings: bool,
+
+    /// Show at most this many fractional digits of the truth, keeping the
+    /// repeating part marked.
+    #[arg(long, value_name = "N")]
+    max_digits: Option<usize>,
+
+    /// Write the truth with exactly this many fractional digits, rounded
+    /// half up, e.g. "0.14286" for 1/7 at 5. At most 100000.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = digits_parser(),
+        conflicts_with = "max_digits"
+    )]
+    digits: Option<usize>,
+
+    /// Truncate the truth whose repeating digits are longer than this,
+    /// rather than finding them [default: 100000].
+    #[arg(long, value_name = "N")]
+    max_period: Option<usize>,
+
+    /// Also show the truth in this base, from 2 to 36.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(2..=36)
+    )]
+    base: Option<u32>,
+
+    /// Style of the reports.
+    #[arg(long, value_enum, default_value_t = Format::Pretty)]
+    format: Format,
+
+    /// Key bindings of the line editor.
+    #[arg(long, value_enum, default_value_t = Keybindings::Emacs)]
+    keybindings: Keybindings,
+
+    /// Maximum number of entries kept in the history file.
+    #[arg(long, default_value_t = 100)]
+    history_size: usize,
+
+    /// History file [default: $XDG_STATE_HOME/feather-repl/history, or
+    /// ~/.float_repl_history].
+    #[arg(long, value_name = "PATH")]
+    history_file: Option<PathBuf>,
+
+    /// Neither read nor write the history, nor keep the lines for recall.
+    #[arg(long, conflicts_with = "history_file")]
+    no_history: bool,
+
+    /// Evaluate the expression, print the report and exit, with a non-zero
+    /// status on a parse or evaluation error.
+    #[arg(long, value_name = "EXPR")]
+    eval: Option<String>,
+
+    /// Evaluate each line of the file, skipping the blank ones and the
+    /// comments starting with "#", print the results with a summary and
+    /// exit, with a non-zero status if any line failed. A line "x => y"
+    /// asserts that the truths of x and y are equal.
+    #[arg(long, value_name = "PATH")]
+    batch: Option<PathBuf>,
+
+    /// Let the assertions "x => y" of --batch pass when
+    /// |x - y| / max(|x|, |y|) <= R, computed exactly, e.g. "1e-4".
+    #[arg(long, value_name = "R", value_parser = parse_epsilon)]
+    epsilon: Option<BigRational>,
+
+    /// Time the decimal expansion of the value of the expression, e.g.
+    /// "1/982451653", and exit. A period longer than --max-period is not
+    /// expanded.
+    #[arg(long, value_name = "EXPR")]
+    benchmark_decimal: Option<String>,
+
+    /// Print without colors, as when the NO_COLOR environment variable is
+    /// set.
+    #[arg(long)]
+    no_color: bool,
+
+    /// Print a sample of each color and exit.
+    #[arg(long)]
+    color_test: bool,
+
+    /// Print the saved history and exit.
+    #[arg(long)]
+    dump_history: bool,
+
+    /// Print the functions with the number of arguments they take, and exit.
+    #[arg(long)]
+    list_functions: bool,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    /// Colored panels.
+    Pretty,
+    /// Color-free markdown tables, for pasting into issues.
+    Markdown,
+    /// A JSON object per line, for the tools reading the results.
+    Json,
+}
+
+impl From<Format> for OutputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Pretty => OutputFormat::Pretty,
+            Format::Markdown => OutputFormat::Markdown,
+            Format::Json => OutputFormat::Json,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Unit {
+    Ratio,
+    Percent,
+    Ulp,
+    /// Decibels, 20 * log10(|rel|).
+    Db,
+}
+
+impl From<Unit> for ErrorUnit {
+    fn from(unit: Unit) -> Self {
+        match unit {
+            Unit::Ratio => ErrorUnit::Ratio,
+            Unit::Percent => ErrorUnit::Percent,
+            Unit::Ulp => ErrorUnit::Ulp,
+            Unit::Db => ErrorUnit::Db,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum FloatTy {
+    F64,
+    /// Also show the value computed in f32.
+    F32,
+}
+
+impl From<FloatTy> for Precision {
+    fn from(ty: FloatTy) -> Self {
+        match ty {
+            FloatTy::F64 => Precision::F64,
+            FloatTy::F32 => Precision::F32,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Keybindings {
+    Emacs,
+    Vi,
+}
+
+impl From<Keybindings> for EditMode {
+    fn from(kb: Keybindings) -> Self {
+        match kb {
+            Keybindings::Emacs => EditMode::Emacs,
+            Keybindings::Vi => EditMode::Vi,
+        }
+    }
+}
+
+/// Parses the non-negative tolerance of `--epsilon`, e.g. `1e-4`.
+fn parse_epsilon(s: &str) -> std::result::Result<BigRational, String> {
+    let dec: DecimalTuple =
+        s.parse().map_err(|_| format!("not a decimal: {s}"))?;
+    let eps = BigRational::from(dec);
+    if eps.is_negative() {
+        return Err(format!("negative tolerance: {s}"));
+    }
+    Ok(eps)
+}
+
+/// Parses the fractional digits of `--digits` and `--show-rounding`, up to
+/// `MAX_DIGITS`.
+fn digits_parser() -> clap::builder::RangedU64ValueParser<usize> {
+    clap::builder::RangedU64ValueParser::new().range(..=MAX_DIGITS as u64)
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
-    repl(ReplOptions::default().with_estimate(args.estimate))
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    if args.no_color || no_color {
+        yansi::Paint::disable();
+    }
+    set_palette(Palette::from_env());
+    if args.color_test {
+        print!("{}", color_test());
+        return Ok(());
+    }
+    let opts = ReplOptions::default()
+        .with_estimate(args.estimate)
+        .with_estimate_depth(args.estimate_depth)
+        .with_format(args.format.into())
+        .with_rounding(args.show_rounding)
+        .with_max_terms(args.max_terms)
+        .with_width(args.max_width.or_else(terminal_width))
+        .with_strict_literals(args.strict_literals)
+        .with_mixed_fractions(args.mixed_fractions)
+        .with_group_repeats(args.group_repeats)
+        .with_group_separator(Some(args.group_separator))
+        .with_dyadic(args.dyadic)
+        .with_compact_zero(args.compact_zero)
+        .with_error_unit(args.error_unit.map(Into::into))
+        .with_precision(args.precision.into())
+        .with_timeout(args.timeout.map(Duration::from_millis))
+        .with_timings(args.timings)
+        .with_max_depth(args.max_depth)
+        .with_digit_limit(args.max_digits)
+        .with_digits(args.digits)
+        .with_max_period(args.max_period)
+        .with_base(args.base)
+        .with_edit_mode(args.keybindings.into())
+        .with_history_size(args.history_size)
+        .with_history_file(args.history_file)
+        .with_history(!args.no_history)
+        .with_epsilon(args.epsilon);
+    if args.dump_history {
+        // nothing to print without a history file
+        return match opts.history_file() {
+            Some(path) => dump_history(&path, &mut std::io::stdout()),
+            None => Ok(()),
+        };
+    }
+    if args.list_functions {
+        return Ok(list_functions(&mut std::io::stdout())?);
+    }
+    if let Some(expr) = args.benchmark_decimal {
+        if !benchmark_once(&expr, &opts, &mut std::io::stdout())? {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(path) = args.batch {
+        if !run_batch(&path, &opts)?.is_success() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(expr) = args.eval {
+        if eval_once(&expr, &opts).is_err() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    repl(opts)
 }
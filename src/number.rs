@@ -1,7 +1,10 @@
+use std::cmp::Ordering;
+use std::num::ParseIntError;
 use std::str::FromStr;
 use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
-use num::{One, Signed, Zero};
+use num::{Integer, One, Signed, Zero};
 use num_bigint::{
     BigInt, BigUint, ParseBigIntError,
     Sign::{self, Minus, NoSign, Plus},
@@ -9,7 +12,10 @@ use num_bigint::{
 use num_rational::BigRational;
 use regex::Regex;
 
-use crate::utils::{cycle_mu_lambda, IterDiffIndex};
+use crate::{
+    constants::{ALTERNATE_DIGITS, MAX_EXPONENT},
+    utils::{cycle_mu_lambda_bounded, IterDiffIndex},
+};
 
 /// Tuple representing a decimal number.
 ///
@@ -25,6 +31,21 @@ pub struct DecimalTuple {
     frac_rep: Vec<u8>,
 }
 
+/// How `DecimalTuple::round_to` rounds the discarded digits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    /// To the nearest; ties away from zero.
+    HalfUp,
+    /// To the nearest; ties to the even digit.
+    HalfEven,
+    /// Toward negative infinity.
+    Floor,
+    /// Toward positive infinity.
+    Ceil,
+    /// Toward zero.
+    Truncate,
+}
+
 impl Default for DecimalTuple {
     fn default() -> Self {
         DecimalTuple {
@@ -100,17 +121,13 @@ impl DecimalTuple {
     /// - `lcp_len(-1.0, -10.0)`: 1 (minus sign)
     /// - `lcp_len(-0.01, 0.0)`: 4 (with properly padded)
     /// - `lcp_len(-0.1, 0.1)`: 0 (strictly opposite signs)
+    /// - `lcp_len(0, -0.1)`: 2 (zero goes with either sign)
     pub fn lcp_len(&self, other: &DecimalTuple) -> Option<usize> {
-        let (sgn_l, uint_l) = (self.sign, &self.int);
-        let (sgn_r, uint_r) = (other.sign, &other.int);
-
-        match (sgn_l, sgn_r) {
-            (x, y) if x == y => {}
-            (NoSign, Plus) | (NoSign, Minus) if uint_r.is_zero() => {}
-            (Plus, NoSign) | (Minus, NoSign) if uint_l.is_zero() => {}
-            _ => return Some(0),
+        if !self.sign_compatible(other) {
+            return Some(0);
         }
 
+        let (uint_l, uint_r) = (&self.int, &other.int);
         let s_uint_l = uint_l.to_string();
         let s_uint_r = uint_r.to_string();
 
@@ -119,32 +136,65 @@ impl DecimalTuple {
         } else if uint_l != uint_r {
             s_uint_l.bytes().iter_diff_index(s_uint_r.bytes())
         } else {
-            let len_l =
-                s_uint_l.len() + 1 + self.frac_once.len() + self.frac_rep.len();
-            let len_r = s_uint_r.len()
-                + 1
-                + other.frac_once.len()
-                + other.frac_rep.len();
-            let bound = 2 * len_l.max(len_r);
-            let left = s_uint_l
-                .bytes()
-                .map(|b| b - b'0')
-                .chain(Some(b'.'))
-                .chain(self.frac_once.iter().copied())
-                .chain(self.frac_rep.iter().copied().cycle())
-                .chain(std::iter::repeat(0))
-                .take(bound);
-            let right = s_uint_r
-                .bytes()
-                .map(|b| b - b'0')
-                .chain(Some(b'.'))
-                .chain(other.frac_once.iter().copied())
-                .chain(other.frac_rep.iter().copied().cycle())
-                .chain(std::iter::repeat(0))
-                .take(bound);
+            let width = s_uint_l.len();
+            let bound = 2 * (width + 1 + self.frac_len().max(other.frac_len()));
+            let left = self.digits_padded(width).take(bound);
+            let right = other.digits_padded(width).take(bound);
             left.iter_diff_index(right)
         };
-        tmp.map(|x| if sgn_l == Minus { x + 1 } else { x })
+        tmp.map(|x| if self.sign == Minus { x + 1 } else { x })
+    }
+
+    /// Similar to `lcp_len`, but the integer parts are right-aligned by
+    /// magnitude, i.e. the shorter one is padded with leading zeros.
+    ///
+    /// The length is counted in the padded representation.
+    ///
+    /// note:
+    ///
+    /// - `lcp_len_aligned(10.0, 10.5)`: 3
+    /// - `lcp_len_aligned(10.0, 1.0)`: 0 (compared as `10.0` and `01.0`)
+    /// - `lcp_len_aligned(1.25, 1.5)`: 2
+    /// - `lcp_len_aligned(-0.5, -10.5)`: 1 (minus sign)
+    pub fn lcp_len_aligned(&self, other: &DecimalTuple) -> Option<usize> {
+        if !self.sign_compatible(other) {
+            return Some(0);
+        }
+
+        let width_l = self.int.to_string().len();
+        let width_r = other.int.to_string().len();
+        let width = width_l.max(width_r);
+        let bound = 2 * (width + 1 + self.frac_len().max(other.frac_len()));
+        let left = self.digits_padded(width).take(bound);
+        let right = other.digits_padded(width).take(bound);
+        let tmp = left.iter_diff_index(right);
+        tmp.map(|x| if self.sign == Minus { x + 1 } else { x })
+    }
+
+    /// Whether the signs do not strictly differ, regarding zero as having
+    /// both signs.
+    fn sign_compatible(&self, other: &DecimalTuple) -> bool {
+        match (self.sign, other.sign) {
+            (x, y) if x == y => true,
+            (NoSign, Plus) | (NoSign, Minus) => other.int.is_zero(),
+            (Plus, NoSign) | (Minus, NoSign) => self.int.is_zero(),
+            _ => false,
+        }
+    }
+
+    fn frac_len(&self) -> usize { self.frac_once.len() + self.frac_rep.len() }
+
+    /// Digits of the representation (`b'.'` for the decimal point), with the
+    /// integer part padded to `width` and followed by infinitely many zeros.
+    fn digits_padded(&self, width: usize) -> impl Iterator<Item = u8> + '_ {
+        let int = self.int.to_string();
+        let pad = width.saturating_sub(int.len());
+        std::iter::repeat_n(0, pad)
+            .chain(int.into_bytes().into_iter().map(|b| b - b'0'))
+            .chain(Some(b'.'))
+            .chain(self.frac_once.iter().copied())
+            .chain(self.frac_rep.iter().copied().cycle())
+            .chain(std::iter::repeat(0))
     }
 
     pub fn is_integer(&self) -> bool {
@@ -152,14 +202,239 @@ impl DecimalTuple {
     }
 
     pub fn is_repetitive(&self) -> bool { !self.frac_rep.is_empty() }
+
+    /// -1, 0, or 1 by the sign; 0 for zero, which has `NoSign`.
+    pub fn signum(&self) -> i8 {
+        match self.sign {
+            Minus => -1,
+            NoSign => 0,
+            Plus => 1,
+        }
+    }
+
+    /// Whether less than zero; zero is neither negative nor positive.
+    pub fn is_negative(&self) -> bool { self.sign == Minus }
+
+    /// Whether greater than zero.
+    pub fn is_positive(&self) -> bool { self.sign == Plus }
+
+    /// Whether this is the unique form of its value, which `Display` and
+    /// `FromStr` round-trip: the repeating digits are neither all `9` nor
+    /// all `0`, they are the shortest and start as early as possible, a
+    /// terminating fraction has no trailing zeros, and only zero has
+    /// `NoSign`. The integer part has no leading zeros by its type.
+    pub fn is_canonical(&self) -> bool {
+        let (once, rep) = (&self.frac_once, &self.frac_rep);
+        let uniform = |d| !rep.is_empty() && rep.iter().all(|&x| x == d);
+        let n = rep.len();
+        let shortest = (1..n).all(|k| n % k != 0 || rep[k..] != rep[..n - k]);
+        let earliest = once.last().is_none() || once.last() != rep.last();
+        let trimmed = !rep.is_empty() || once.last() != Some(&0);
+        (self.sign == NoSign) == self.is_zero()
+            && !uniform(9)
+            && !uniform(0)
+            && shortest
+            && earliest
+            && trimmed
+    }
+
+    fn is_zero(&self) -> bool { self.int.is_zero() && self.is_integer() }
+
+    pub fn sign(&self) -> Sign { self.sign }
+
+    /// The integer part of the magnitude, e.g. 12 for -12.3(4).
+    pub fn integer_part(&self) -> &BigUint { &self.int }
+
+    /// The fractional digits before the repeating ones, e.g. `[3]` for
+    /// -12.3(4).
+    pub fn once_digits(&self) -> &[u8] { &self.frac_once }
+
+    /// The repeating fractional digits, e.g. `[4]` for -12.3(4); empty if
+    /// the fraction terminates.
+    pub fn rep_digits(&self) -> &[u8] { &self.frac_rep }
+
+    /// Sets the sign. Zero always has `NoSign`, and a nonzero value given
+    /// `NoSign` becomes positive.
+    pub fn with_sign(mut self, sign: Sign) -> Self {
+        self.sign = match sign {
+            _ if self.is_zero() => NoSign,
+            NoSign => Plus,
+            _ => sign,
+        };
+        self
+    }
+
+    /// Number of the repeating fractional digits, e.g. 6 for 1/7 and 0 for
+    /// `0.(9)`, which is normalized to 1.
+    pub fn period(&self) -> usize { self.frac_rep.len() }
+
+    /// Number of the fractional digits before the repeating ones.
+    pub fn preperiod(&self) -> usize { self.frac_once.len() }
+
+    /// Formats with at most `limit` fractional digits before the repeating
+    /// part, which is rotated to start after them, e.g. `0.33333(3...)` for
+    /// 1/3 at limit 5. A terminating value cut short ends with `...`, and a
+    /// repeating one cut within the non-repeating digits with `(...)`.
+    pub fn to_string_limited(&self, limit: usize) -> String {
+        let once = self.frac_once.len();
+        if !self.is_repetitive() && once <= limit {
+            return self.to_string();
+        }
+
+        // the sign and the integer part, which is `-0` for -1/3
+        let head = DecimalTuple {
+            frac_once: vec![],
+            frac_rep: vec![],
+            ..self.clone()
+        };
+        let mut res = format!("{head}.");
+        let digit = |b: &u8| (b + b'0') as char;
+        let frac = self.frac_once.iter().chain(self.frac_rep.iter().cycle());
+        res.extend(frac.take(limit).map(digit));
+        if !self.is_repetitive() {
+            res += "...";
+        } else if once > limit {
+            res += "(...)";
+        } else {
+            let k = (limit - once) % self.frac_rep.len();
+            let rep = self.frac_rep[k..].iter().chain(&self.frac_rep[..k]);
+            res += "(";
+            res.extend(rep.map(digit));
+            res += "...)";
+        }
+        res
+    }
+
+    /// Formats with exactly `places` fractional digits, rounded by `mode`,
+    /// e.g. `0.142857142857` for 1/7 at 12 places and `0.12500` for 1/8 at
+    /// 5 places.
+    pub fn to_decimal_string(
+        &self,
+        places: usize,
+        mode: RoundingMode,
+    ) -> String {
+        let rounded = self.round_to(places, mode);
+        let head = DecimalTuple { frac_once: vec![], ..rounded.clone() };
+        let mut res = head.to_string();
+        if places > 0 {
+            res += ".";
+            let digits = rounded.frac_once.iter().map(|b| (b + b'0') as char);
+            res.extend(digits.chain(std::iter::repeat('0')).take(places));
+        }
+        res
+    }
+
+    /// Multiplies by `10^k`, e.g. 1.5 to 150 for `k = 2`; negative `k`
+    /// shifts the point to the left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `|k|` exceeds `MAX_EXPONENT`; see `checked_scale_pow10`.
+    pub fn scale_pow10(self, k: i32) -> Self {
+        self.checked_scale_pow10(k).expect("exponent out of range")
+    }
+
+    /// `self * 10^k`, or `None` if `|k|` exceeds `MAX_EXPONENT`, as the
+    /// digits or the power would take time and memory in proportion to it.
+    pub fn checked_scale_pow10(self, k: i32) -> Option<Self> {
+        if k.unsigned_abs() > MAX_EXPONENT {
+            return None;
+        }
+        if self.is_repetitive() {
+            let rat = BigRational::from(self);
+            let scale = BigInt::from(10).pow(k.unsigned_abs());
+            let scale = BigRational::from_integer(scale);
+            let rat = if k >= 0 { rat * scale } else { rat / scale };
+            return Some(rat.into());
+        }
+        if self.sign == NoSign {
+            return Some(self);
+        }
+
+        // move the point within the digits of `int` followed by `frac_once`
+        let int_digits = self.int.to_radix_be(10);
+        let point = int_digits.len() as i64 + k as i64;
+        let mut digits = int_digits;
+        digits.extend(self.frac_once);
+        if point <= 0 {
+            let zeros = std::iter::repeat_n(0, (-point) as usize);
+            digits.splice(0..0, zeros);
+        } else if point as usize > digits.len() {
+            digits.resize(point as usize, 0);
+        }
+        let point = point.max(0) as usize;
+        let mut frac_once = digits.split_off(point);
+        while frac_once.last() == Some(&0) {
+            frac_once.pop();
+        }
+        let int = BigUint::from_radix_be(&digits, 10).unwrap_or_default();
+        Some(Self { sign: self.sign, int, frac_once, frac_rep: vec![] })
+    }
+
+    /// Rounds to `places` fractional digits, e.g. 8.451(923076...) to
+    /// 8.4519 at 4 places. The result has no repeating part.
+    pub fn round_to(&self, places: usize, mode: RoundingMode) -> Self {
+        round_rational(&self.to_owned().into(), places, mode).into()
+    }
+}
+
+/// Rounds `rat` to `places` fractional digits, without going through the
+/// digits of `rat`, which may have a long period.
+///
+/// # Panics
+///
+/// Panics if `places` exceeds `u32::MAX`.
+pub fn round_rational(
+    rat: &BigRational,
+    places: usize,
+    mode: RoundingMode,
+) -> BigRational {
+    let places = u32::try_from(places).expect("places out of range");
+    let scale = BigInt::from(10).pow(places);
+    let scale = BigRational::from_integer(scale);
+    let x = rat * &scale;
+    let int = match mode {
+        RoundingMode::HalfUp => x.round(),
+        RoundingMode::HalfEven => {
+            let floor = x.floor();
+            let half = BigRational::new(1.into(), 2.into());
+            match (&x - &floor).cmp(&half) {
+                Ordering::Less => floor,
+                Ordering::Greater => floor + BigRational::one(),
+                Ordering::Equal if floor.to_integer().is_even() => floor,
+                Ordering::Equal => floor + BigRational::one(),
+            }
+        }
+        RoundingMode::Floor => x.floor(),
+        RoundingMode::Ceil => x.ceil(),
+        RoundingMode::Truncate => x.trunc(),
+    };
+    int / scale
 }
 
+/// `0.(3...)` for 1/3, or with `{:#}`, the digits written out inline and
+/// cut after the precision (`ALTERNATE_DIGITS` unless given) with `…`, e.g.
+/// `0.333…` by `{:#.3}`.
 impl std::fmt::Display for DecimalTuple {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.sign == Minus {
             write!(f, "-")?;
         }
         write!(f, "{}", self.int)?;
+        if f.alternate() {
+            let places = f.precision().unwrap_or(ALTERNATE_DIGITS);
+            let frac =
+                self.frac_once.iter().chain(self.frac_rep.iter().cycle());
+            let digits: String =
+                frac.take(places).map(|&b| (b + b'0') as char).collect();
+            if !digits.is_empty() {
+                write!(f, ".{digits}")?;
+            }
+            if self.is_repetitive() || self.frac_once.len() > places {
+                write!(f, "…")?;
+            }
+            return Ok(());
+        }
         let mut tmp: String =
             self.frac_once.iter().map(|&b| (b + b'0') as char).collect();
         if !self.frac_rep.is_empty() {
@@ -187,9 +462,18 @@ impl From<DecimalTuple> for BigRational {
 
 impl From<BigRational> for DecimalTuple {
     fn from(rat: BigRational) -> Self {
+        Self::from_rational(rat, usize::MAX).unwrap()
+    }
+}
+
+impl DecimalTuple {
+    /// Converts `rat` unless its period is longer than `max_period`, which
+    /// would take time and memory in proportion, e.g. 1/p for a large prime
+    /// p has a period up to `p - 1`.
+    pub fn from_rational(rat: BigRational, max_period: usize) -> Option<Self> {
         let (sgn, mag) = (rat.signum(), rat.abs());
         if sgn.is_zero() {
-            return Self::zero();
+            return Some(Self::zero());
         }
         let sign = if sgn.is_negative() { Minus } else { Plus };
 
@@ -203,8 +487,16 @@ impl From<BigRational> for DecimalTuple {
             .skip(1)
             .map(|x| x.0.try_into().unwrap())
         };
-        let (mu, lambda) =
-            cycle_mu_lambda(num % den, |x: &BigInt| x * 10 % den);
+        // the digits before the period are fewer than the bits of `den`, and
+        // a terminating one ends with the period of `0`
+        let max_lambda = max_period.max(1);
+        let limit = max_lambda.saturating_add(den.bits() as usize);
+        let (mu, lambda) = cycle_mu_lambda_bounded(
+            num % den,
+            |x: &BigInt| x * 10 % den,
+            limit,
+        )
+        .filter(|&(_, lambda)| lambda <= max_lambda)?;
 
         let mut it = div_iter(num.to_owned(), den.to_owned());
         let frac_once: Vec<_> = it.by_ref().take(mu).collect();
@@ -212,19 +504,288 @@ impl From<BigRational> for DecimalTuple {
         if frac_rep == [0] {
             frac_rep.clear();
         }
+        if frac_rep.len() > max_period {
+            return None;
+        }
+
+        let res = Self { sign, int, frac_once, frac_rep };
+        debug_assert!(res.is_canonical(), "{res:?}");
+        Some(res)
+    }
+}
+
+impl DecimalTuple {
+    /// `self / rhs`, or `None` if `rhs` is zero.
+    ///
+    /// The quotient is expanded to its whole period, as by the operators.
+    /// Divide the `BigRational`s and use `from_rational` to bound it.
+    pub fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        if rhs.sign == NoSign {
+            return None;
+        }
+        Some((rat(self) / rat(rhs)).into())
+    }
+}
+
+/// The arithmetic through `BigRational`, normalized as by `From`.
+///
+/// The conversion back is the unbounded `From<BigRational>`, not
+/// `from_rational(_, max_period)`, so the whole period is expanded. For
+/// example, `1 / p` of `DecimalTuple`s for a large prime `p` takes time and
+/// memory in proportion to its period, up to `p - 1` digits.
+macro_rules! impl_op {
+    ($op:ident, $method:ident, |$lhs:ident, $rhs:ident| $body:expr) => {
+        impl std::ops::$op<&DecimalTuple> for &DecimalTuple {
+            type Output = DecimalTuple;
+            fn $method(self, rhs: &DecimalTuple) -> DecimalTuple {
+                let ($lhs, $rhs) = (self, rhs);
+                $body
+            }
+        }
+        impl std::ops::$op<DecimalTuple> for &DecimalTuple {
+            type Output = DecimalTuple;
+            fn $method(self, rhs: DecimalTuple) -> DecimalTuple {
+                self.$method(&rhs)
+            }
+        }
+        impl std::ops::$op<&DecimalTuple> for DecimalTuple {
+            type Output = DecimalTuple;
+            fn $method(self, rhs: &DecimalTuple) -> DecimalTuple {
+                (&self).$method(rhs)
+            }
+        }
+        impl std::ops::$op<DecimalTuple> for DecimalTuple {
+            type Output = DecimalTuple;
+            fn $method(self, rhs: DecimalTuple) -> DecimalTuple {
+                (&self).$method(&rhs)
+            }
+        }
+    };
+}
+
+fn rat(dec: &DecimalTuple) -> BigRational { dec.to_owned().into() }
+
+impl_op!(Add, add, |lhs, rhs| (rat(lhs) + rat(rhs)).into());
+impl_op!(Sub, sub, |lhs, rhs| (rat(lhs) - rat(rhs)).into());
+impl_op!(Mul, mul, |lhs, rhs| (rat(lhs) * rat(rhs)).into());
+impl_op!(Div, div, |lhs, rhs| {
+    lhs.checked_div(rhs).expect("division by a zero `DecimalTuple`")
+});
+
+impl PartialOrd for DecimalTuple {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares the represented values, so `0.(9)` equals `1`.
+impl Ord for DecimalTuple {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let lhs = BigRational::from(self.to_owned());
+        let rhs = BigRational::from(other.to_owned());
+        lhs.cmp(&rhs)
+    }
+}
+
+/// `floor(log10(x))` of the positive `x`, without going through floats.
+pub fn ilog10(x: &BigRational) -> i32 {
+    let ten = BigRational::from_integer(10.into());
+    let digits = |n: &BigInt| n.to_string().len() as i32;
+    let mut e = digits(x.numer()) - digits(x.denom());
+    while ten.pow(e) > *x {
+        e -= 1;
+    }
+    while ten.pow(e + 1) <= *x {
+        e += 1;
+    }
+    e
+}
+
+/// `rat` as `m * 10^e` with `1 <= |m| < 10`, unless `rat` is zero or the
+/// period of `m` is longer than `max_period`.
+pub fn to_scientific(
+    rat: &BigRational,
+    max_period: usize,
+) -> Option<(DecimalTuple, i32)> {
+    if rat.is_zero() {
+        return None;
+    }
+    let e = ilog10(&rat.abs());
+    let scale = BigInt::from(10).pow(e.unsigned_abs());
+    let scale = BigRational::from_integer(scale);
+    let m = if e >= 0 { rat / scale } else { rat * scale };
+    Some((DecimalTuple::from_rational(m, max_period)?, e))
+}
+
+/// Coefficients of the (simple) continued fraction.
+///
+/// For example, 355/113 = 3 + 1/(7 + 1/16) yields `[3, 7, 16]`. Each
+/// coefficient is obtained by the floor, so -7/3 yields `[-3, 1, 2]`, and an
+/// integer `n` yields `[n]`.
+pub fn continued_fraction(rat: &BigRational) -> Vec<BigInt> {
+    let (mut num, mut den) = (rat.numer().to_owned(), rat.denom().to_owned());
+    let mut res = vec![];
+    while !den.is_zero() {
+        let (q, r) = num.div_mod_floor(&den);
+        res.push(q);
+        (num, den) = (den, r);
+    }
+    res
+}
+
+/// Times the conversion of `rat` into `DecimalTuple`, returning
+/// `DecimalTuple::preperiod` and `DecimalTuple::period`, or `None` if the
+/// period is longer than `max_period`.
+pub fn benchmark_decimal(
+    rat: &BigRational,
+    max_period: usize,
+) -> (Option<(usize, usize)>, Duration) {
+    let start = Instant::now();
+    let dec = DecimalTuple::from_rational(rat.to_owned(), max_period);
+    let period = dec.map(|dec| (dec.preperiod(), dec.period()));
+    (period, start.elapsed())
+}
+
+/// Whether `a` and `b` agree within the relative tolerance `eps`, i.e.
+/// `|a - b| / max(|a|, |b|) <= eps`, computed exactly. Equal values agree
+/// whatever `eps` is, zeros included.
+pub fn approx_eq(a: &BigRational, b: &BigRational, eps: &BigRational) -> bool {
+    if a == b {
+        return true;
+    }
+    let larger = a.abs().max(b.abs());
+    (a - b).abs() <= eps * larger
+}
+
+/// The truncated and the rounded values at `places` fractional digits, e.g.
+/// `(0.666, 0.667)` for 2/3 at 3 places.
+pub fn value_at_precision(
+    rat: &BigRational,
+    places: usize,
+) -> (DecimalTuple, DecimalTuple) {
+    (
+        round_rational(rat, places, RoundingMode::Truncate).into(),
+        round_rational(rat, places, RoundingMode::HalfUp).into(),
+    )
+}
+
+/// Formats `rat` in `base` (2 to 36) with the same repeating notation as
+/// `DecimalTuple`, e.g. `0.(01...)` for 1/3 in base 2.
+pub fn to_base_string(rat: &BigRational, base: u32) -> String {
+    to_base_string_bounded(rat, base, usize::MAX).unwrap()
+}
+
+/// The sign, the integer part in `base`, and the fractional digits in `base`
+/// one after another, without an end for the repeating ones.
+fn base_digits(
+    rat: &BigRational,
+    base: u32,
+) -> (String, impl Iterator<Item = char>) {
+    assert!((2..=36).contains(&base), "base out of range: {base}");
+    let mag = rat.abs();
+    let (int, frac) = (mag.to_integer(), mag.fract());
+    let (num, den) = (frac.numer().to_owned(), frac.denom().to_owned());
+
+    let sign = if rat.is_negative() { "-" } else { "" };
+    let head = format!("{sign}{}", int.to_str_radix(base));
+    let digit =
+        move |d: BigInt| char::from_digit(d.try_into().unwrap(), base).unwrap();
+    let it = std::iter::successors(Some(num), {
+        let den = den.clone();
+        move |x| Some(x * base % &den)
+    });
+    (head, it.map(move |x| digit(x * base / &den)))
+}
+
+/// Same as `to_base_string`, unless the period is longer than
+/// `max_period`, which would take time and memory in proportion, as in
+/// `DecimalTuple::from_rational`.
+pub fn to_base_string_bounded(
+    rat: &BigRational,
+    base: u32,
+    max_period: usize,
+) -> Option<String> {
+    let frac = rat.abs().fract();
+    let (num, den) = (frac.numer(), frac.denom());
+    // the digits before the period are fewer than the bits of `den`
+    let max_lambda = max_period.max(1);
+    let limit = max_lambda.saturating_add(den.bits() as usize);
+    let (mu, lambda) = cycle_mu_lambda_bounded(
+        num.to_owned(),
+        |x: &BigInt| x * base % den,
+        limit,
+    )
+    .filter(|&(_, lambda)| lambda <= max_lambda)?;
+
+    let (mut res, mut it) = base_digits(rat, base);
+    let once: String = it.by_ref().take(mu).collect();
+    let mut rep: String = it.take(lambda).collect();
+    if rep == "0" {
+        rep.clear();
+    }
+    if !once.is_empty() || !rep.is_empty() {
+        res += &format!(".{once}");
+        if !rep.is_empty() {
+            res += &format!("({rep}...)");
+        }
+    }
+    Some(res)
+}
+
+/// `rat` in `base` truncated to `places` fractional digits, e.g. `0.0101`
+/// for 1/3 in base 2 at 4 places, for the ones with too long a period.
+pub fn to_base_string_truncated(
+    rat: &BigRational,
+    base: u32,
+    places: usize,
+) -> String {
+    let (head, it) = base_digits(rat, base);
+    let frac: String = it.take(places).collect();
+    if frac.is_empty() { head } else { format!("{head}.{frac}") }
+}
+
+/// Parses the output of `to_base_string`; `(01)` and `(01...)` are both
+/// accepted for the repeating part.
+pub fn parse_base(s: &str, base: u32) -> Option<BigRational> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    let (once, rep) = match frac.split_once('(') {
+        Some((once, rep)) => {
+            let rep = rep.strip_suffix(')')?;
+            (once, rep.strip_suffix("...").unwrap_or(rep))
+        }
+        None => (frac, ""),
+    };
+    let parse = |s: &str| -> Option<BigInt> {
+        if s.is_empty() {
+            Some(BigInt::zero())
+        } else {
+            BigInt::parse_bytes(s.as_bytes(), base)
+        }
+    };
+    let pow = |k: usize| BigInt::from(base).pow(k as u32);
 
-        Self { sign, int, frac_once, frac_rep }
+    let mut res = BigRational::from(parse(int)?);
+    res += BigRational::new(parse(once)?, pow(once.len()));
+    if !rep.is_empty() {
+        let den = pow(once.len()) * (pow(rep.len()) - 1);
+        res += BigRational::new(parse(rep)?, den);
     }
+    Some(if neg { -res } else { res })
 }
 
 const DECIMAL_PATTERN: &str = r"(?x)
 ^
 (?P<SIGN>[+-])?
-(?P<INT>-?[0-9]+)
+(?P<INT>-?[0-9]+(?:_[0-9]+)*)
 (?:
-    \.(?P<ONCE>[0-9]+)?
-    (?P<REP>\([0-9]+\.*\))?
+    \.(?P<ONCE>[0-9]+(?:_[0-9]+)*)?
+    (?P<REP>\([0-9]+(?:_[0-9]+)*\.*\))?
 )?
+(?P<EXP>[eE][+-]?[0-9]+)?
 $
 ";
 
@@ -232,6 +793,9 @@ $
 pub enum DecimalTupleParseError {
     MatchFailed,
     BigIntError(ParseBigIntError),
+    ExponentError(ParseIntError),
+    /// The exponent is larger than `MAX_EXPONENT` in magnitude.
+    ExponentOutOfRange(i32),
 }
 use DecimalTupleParseError::*;
 
@@ -244,7 +808,7 @@ impl FromStr for DecimalTuple {
 
         let neg = caps.name("SIGN").map(|c| c.as_str() == "-").unwrap_or(false);
         let sign = if neg { Minus } else { Plus };
-        let cap_int = caps.name("INT").unwrap().as_str();
+        let cap_int = caps.name("INT").unwrap().as_str().replace('_', "");
         let int: BigUint = cap_int.parse().map_err(BigIntError)?;
 
         let collect_digits = |name: &str| {
@@ -252,14 +816,19 @@ impl FromStr for DecimalTuple {
                 .map(|c| c.as_str().bytes())
                 .into_iter()
                 .flatten()
-                .filter(|b| (b'0'..=b'9').contains(b))
+                .filter(|b| b.is_ascii_digit())
                 .map(|b| b - b'0')
                 .collect::<Vec<_>>()
         };
         let once = collect_digits("ONCE");
         let rep = collect_digits("REP");
+        let exp = match caps.name("EXP") {
+            Some(c) => c.as_str()[1..].parse().map_err(ExponentError)?,
+            None => 0,
+        };
 
-        Ok(Self::new(sign, int, once, rep))
+        let dec = Self::new(sign, int, once, rep);
+        dec.checked_scale_pow10(exp).ok_or(ExponentOutOfRange(exp))
     }
 }
 
@@ -267,6 +836,7 @@ impl FromStr for DecimalTuple {
 mod tests_parse {
     use super::*;
 
+    #[allow(clippy::type_complexity)]
     const TEST_SUITE_OK: &[(&str, (Sign, u64, &[u8], &[u8]))] = &[
         // simple
         ("1.2", (Plus, 1, &[2], &[])),
@@ -295,11 +865,20 @@ mod tests_parse {
         // leading/trailing zeros
         ("00", (NoSign, 0, &[], &[])),
         ("001.10(0)", (Plus, 1, &[1], &[])),
+        // digit separators
+        ("1_000.0_1(2_3)", (Plus, 1000, &[0, 1], &[2, 3])),
+        // exponent
+        ("1e10", (Plus, 10_000_000_000, &[], &[])),
+        ("1.5E-2", (Plus, 0, &[0, 1, 5], &[])),
+        ("-2.5e+1", (Minus, 25, &[], &[])),
+        ("0.(3)e1", (Plus, 3, &[], &[3])),
+        ("0e5", (NoSign, 0, &[], &[])),
     ];
 
     const TEST_SUITE_ERR: &[&str] = &[
         //
-        "0.11()", "+-0", "@", "1.2.3", "0.999...", "0.1((1))", " 1 ",
+        "0.11()", "+-0", "@", "1.2.3", "0.999...", "0.1((1))", " 1 ", "1__0",
+        "_1", "1_", "1_.5", "1._5", "0.(1_)", "1e", "1e+", "1e1.5", "1e-",
     ];
 
     #[test]
@@ -320,6 +899,12 @@ mod tests_parse {
         for s in TEST_SUITE_ERR {
             assert!(s.parse::<DecimalTuple>().is_err());
         }
+        let actual = "1e99999999999".parse::<DecimalTuple>();
+        assert!(matches!(actual, Err(ExponentError(_))));
+        let actual = "1e2000000000".parse::<DecimalTuple>();
+        assert_eq!(actual, Err(ExponentOutOfRange(2_000_000_000)));
+        let actual = "0.(3)e-100001".parse::<DecimalTuple>();
+        assert_eq!(actual, Err(ExponentOutOfRange(-100_001)));
     }
 }
 
@@ -340,6 +925,8 @@ mod tests_lcp {
         (("0", "0.001"), Some(4)),
         (("-0.001", "0"), Some(5)),
         (("-0.001", "0.001"), Some(0)),
+        (("0", "0.1"), Some(2)),
+        (("0", "-0.1"), Some(2)),
         (("1", "1"), None),
         (("-1", "-1"), None),
     ];
@@ -353,4 +940,562 @@ mod tests_lcp {
             assert_eq!(lhs.lcp_len(&rhs), expected);
         }
     }
+
+    const TEST_SUITE_ALIGNED: &[((&str, &str), Option<usize>)] = &[
+        (("10.0", "10.5"), Some(3)),
+        (("10.5", "10.0"), Some(3)),
+        (("10.0", "1.0"), Some(0)),
+        (("1.25", "1.5"), Some(2)),
+        (("-0.5", "-10.5"), Some(1)),
+        (("1.0", "-1.0"), Some(0)),
+        (("0", "0.001"), Some(4)),
+        (("10", "10"), None),
+    ];
+
+    #[test]
+    fn test_aligned() {
+        for &((lhs, rhs), expected) in TEST_SUITE_ALIGNED {
+            let lhs: DecimalTuple = lhs.parse().unwrap();
+            let rhs: DecimalTuple = rhs.parse().unwrap();
+            assert_eq!(lhs.lcp_len_aligned(&rhs), expected, "{lhs} {rhs}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_ord {
+    use super::*;
+
+    const TEST_SUITE: &[((&str, &str), Ordering)] = &[
+        (("0.(3)", "0.334"), Ordering::Less),
+        (("0.(3)", "0.333"), Ordering::Greater),
+        (("0.(9)", "1"), Ordering::Equal),
+        (("-0", "0"), Ordering::Equal),
+        (("-1", "0"), Ordering::Less),
+        (("0", "1"), Ordering::Less),
+        (("-1.5", "-1.(4)"), Ordering::Less),
+        (("10.0", "9.(9)"), Ordering::Equal),
+        (("12.1", "2.9"), Ordering::Greater),
+    ];
+
+    #[test]
+    fn test() {
+        for &((lhs, rhs), expected) in TEST_SUITE {
+            let lhs: DecimalTuple = lhs.parse().unwrap();
+            let rhs: DecimalTuple = rhs.parse().unwrap();
+            assert_eq!(lhs.cmp(&rhs), expected, "{lhs} {rhs}");
+            assert_eq!(rhs.cmp(&lhs), expected.reverse(), "{rhs} {lhs}");
+            assert_eq!(lhs == rhs, expected == Ordering::Equal);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_cf {
+    use super::*;
+
+    const TEST_SUITE: &[(&str, &[i64])] = &[
+        ("355/113", &[3, 7, 16]),
+        ("22/7", &[3, 7]),
+        ("1/1", &[1]),
+        ("3", &[3]),
+        ("0", &[0]),
+        ("1/3", &[0, 3]),
+        ("-7/3", &[-3, 1, 2]),
+    ];
+
+    #[test]
+    fn test() {
+        for &(rat, expected) in TEST_SUITE {
+            let rat: BigRational = rat.parse().unwrap();
+            let expected: Vec<_> =
+                expected.iter().map(|&x| BigInt::from(x)).collect();
+            assert_eq!(continued_fraction(&rat), expected);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_approx_eq {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let rat = |s: &str| s.parse::<BigRational>().unwrap();
+        // 0.1 + 0.2 against 0.30001
+        let (a, b) = (rat("3/10"), rat("30001/100000"));
+        assert!(!approx_eq(&a, &b, &BigRational::zero()));
+        assert!(approx_eq(&a, &b, &rat("1/10000")));
+        assert!(!approx_eq(&a, &b, &rat("1/100000")));
+        // the tolerance is relative to the larger one
+        assert!(approx_eq(&rat("1"), &rat("2"), &rat("1/2")));
+        assert!(!approx_eq(&rat("1"), &rat("-1"), &rat("1")));
+        assert!(approx_eq(&rat("0"), &rat("0"), &BigRational::zero()));
+        assert!(!approx_eq(&rat("0"), &rat("1/1000"), &rat("1/2")));
+    }
+}
+
+#[cfg(test)]
+mod tests_round {
+    use super::*;
+    use RoundingMode::*;
+
+    #[allow(clippy::type_complexity)]
+    const TEST_SUITE: &[((&str, usize, RoundingMode), &str)] = &[
+        (("8.451(923076)", 4, HalfUp), "8.4519"),
+        (("8.451(923076)", 2, Floor), "8.45"),
+        (("8.451(923076)", 2, Ceil), "8.46"),
+        (("-8.451(923076)", 2, Floor), "-8.46"),
+        (("-8.451(923076)", 2, Truncate), "-8.45"),
+        // carry into the integer part
+        (("9.99(5)", 2, HalfUp), "10"),
+        (("9.9996", 3, HalfEven), "10"),
+        (("-9.96", 1, HalfUp), "-10"),
+        // ties
+        (("2.5", 0, HalfUp), "3"),
+        (("-2.5", 0, HalfUp), "-3"),
+        (("2.5", 0, HalfEven), "2"),
+        (("3.5", 0, HalfEven), "4"),
+        (("-2.5", 0, HalfEven), "-2"),
+        (("0.125", 2, HalfEven), "0.12"),
+        (("0.135", 2, HalfEven), "0.14"),
+        (("0.1251", 2, HalfEven), "0.13"),
+        // already short enough
+        (("0.5", 3, HalfEven), "0.5"),
+        (("0", 2, Ceil), "0"),
+    ];
+
+    #[test]
+    fn test() {
+        for &((s, places, mode), expected) in TEST_SUITE {
+            let dec: DecimalTuple = s.parse().unwrap();
+            let actual = dec.round_to(places, mode);
+            assert_eq!(actual.to_string(), expected, "{s} {places} {mode:?}");
+            assert!(!actual.is_repetitive());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_alternate {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let test_suite = [
+            ("0.(3)", Some(6), "0.333333…"),
+            ("-0.1(6)", Some(3), "-0.166…"),
+            ("0.(142857)", Some(0), "0…"),
+            ("0.125", Some(5), "0.125"),
+            ("0.125", Some(2), "0.12…"),
+            ("12", Some(2), "12"),
+            ("0.(3)", None, "0.3333333333333333…"),
+            ("1.5", None, "1.5"),
+        ];
+        for (s, places, expected) in test_suite {
+            let dec: DecimalTuple = s.parse().unwrap();
+            let actual = match places {
+                Some(places) => format!("{dec:#.places$}"),
+                None => format!("{dec:#}"),
+            };
+            assert_eq!(actual, expected, "{s} {places:?}");
+        }
+        let dec: DecimalTuple = "0.(3)".parse().unwrap();
+        assert_eq!(format!("{dec:.2}"), "0.(3...)");
+    }
+}
+
+#[cfg(test)]
+mod tests_limited {
+    use super::*;
+
+    const TEST_SUITE: &[((&str, usize), &str)] = &[
+        (("0.(3)", 5), "0.33333(3...)"),
+        (("0.(3)", 0), "0.(3...)"),
+        (("-0.(3)", 2), "-0.33(3...)"),
+        (("0.(142857)", 3), "0.142(857142...)"),
+        (("0.(142857)", 8), "0.14285714(285714...)"),
+        (("8.451(923076)", 4), "8.4519(230769...)"),
+        (("8.451(923076)", 2), "8.45(...)"),
+        (("0.125", 5), "0.125"),
+        (("0.125", 3), "0.125"),
+        (("0.125", 2), "0.12..."),
+        (("12", 2), "12"),
+    ];
+
+    #[test]
+    fn test() {
+        for &((s, limit), expected) in TEST_SUITE {
+            let dec: DecimalTuple = s.parse().unwrap();
+            assert_eq!(dec.to_string_limited(limit), expected, "{s} {limit}");
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for s in ["0.(3)", "0.(142857)", "-8.451(923076)", "1.2(34)"] {
+            let dec: DecimalTuple = s.parse().unwrap();
+            // lossless unless cut within the non-repeating digits
+            for limit in dec.frac_once.len()..10 {
+                let actual = dec.to_string_limited(limit);
+                assert_eq!(actual.parse(), Ok(dec.clone()), "{actual}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_decimal_string {
+    use super::*;
+
+    const TEST_SUITE: &[((&str, usize, RoundingMode), &str)] = &[
+        (("0.(142857)", 12, RoundingMode::HalfUp), "0.142857142857"),
+        (("0.(142857)", 5, RoundingMode::HalfUp), "0.14286"),
+        (("0.(142857)", 5, RoundingMode::Truncate), "0.14285"),
+        (("0.125", 5, RoundingMode::HalfUp), "0.12500"),
+        (("0.125", 2, RoundingMode::HalfEven), "0.12"),
+        (("0.(9)", 3, RoundingMode::HalfUp), "1.000"),
+        (("9.99(9)", 2, RoundingMode::HalfUp), "10.00"),
+        (("0.9996", 3, RoundingMode::HalfUp), "1.000"),
+        (("-0.(6)", 4, RoundingMode::HalfUp), "-0.6667"),
+        (("-0.(6)", 4, RoundingMode::Ceil), "-0.6666"),
+        (("2.5", 0, RoundingMode::HalfEven), "2"),
+        (("12", 3, RoundingMode::HalfUp), "12.000"),
+    ];
+
+    #[test]
+    fn test() {
+        for &((s, places, mode), expected) in TEST_SUITE {
+            let dec: DecimalTuple = s.parse().unwrap();
+            let actual = dec.to_decimal_string(places, mode);
+            assert_eq!(actual, expected, "{s} {places} {mode:?}");
+        }
+        let dec: DecimalTuple = "0.(142857)".parse().unwrap();
+        let actual = dec.to_decimal_string(30, RoundingMode::HalfUp);
+        assert_eq!(actual, format!("0.{}", "142857".repeat(5)));
+    }
+}
+
+#[cfg(test)]
+mod tests_scale {
+    use super::*;
+
+    const TEST_SUITE: &[((&str, i32), &str)] = &[
+        (("1.5", 2), "150"),
+        (("150", -2), "1.5"),
+        (("0.1", 2), "10"),
+        (("-12.34", 1), "-123.4"),
+        (("-12.34", -3), "-0.01234"),
+        (("100", -2), "1"),
+        (("0.001", 3), "1"),
+        (("7", 0), "7"),
+        (("0", 5), "0"),
+        (("0.(3)", 1), "3.(3...)"),
+        (("0.1(6)", -1), "0.01(6...)"),
+    ];
+
+    #[test]
+    fn test() {
+        for &((s, k), expected) in TEST_SUITE {
+            let dec: DecimalTuple = s.parse().unwrap();
+            let actual = dec.clone().scale_pow10(k);
+            assert_eq!(actual.to_string(), expected, "{s} {k}");
+
+            // agrees with the normalized form
+            let scale = BigRational::new(10.into(), 1.into()).pow(k);
+            assert_eq!(actual, (BigRational::from(dec) * scale).into());
+        }
+
+        let dec: DecimalTuple = "1.5".parse().unwrap();
+        assert!(dec.clone().checked_scale_pow10(100_000).is_some());
+        assert_eq!(dec.clone().checked_scale_pow10(100_001), None);
+        assert_eq!(dec.checked_scale_pow10(i32::MIN), None);
+    }
+}
+
+#[cfg(test)]
+mod tests_precision {
+    use super::*;
+
+    #[allow(clippy::type_complexity)]
+    const TEST_SUITE: &[((&str, usize), (&str, &str))] = &[
+        (("2/3", 3), ("0.666", "0.667")),
+        (("-2/3", 3), ("-0.666", "-0.667")),
+        (("1/8", 2), ("0.12", "0.13")),
+        (("1/3", 0), ("0", "0")),
+        (("5/2", 0), ("2", "3")),
+        (("1/4", 5), ("0.25", "0.25")),
+    ];
+
+    #[test]
+    fn test() {
+        for &((rat, places), (trunc, round)) in TEST_SUITE {
+            let rat: BigRational = rat.parse().unwrap();
+            let (t, r) = value_at_precision(&rat, places);
+            assert_eq!(t.to_string(), trunc);
+            assert_eq!(r.to_string(), round);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_ops {
+    use super::*;
+
+    fn dec(s: &str) -> DecimalTuple { s.parse().unwrap() }
+
+    #[test]
+    fn test() {
+        assert_eq!(dec("0.(3)") + dec("0.(6)"), dec("1"));
+        assert_eq!(&dec("0.(3)") + &dec("0.(6)"), dec("1"));
+        assert!((dec("0.(3)") + dec("0.(6)")).is_integer());
+        assert_eq!(dec("0.1") - &dec("0.(1)"), dec("-0.0(1)"));
+        assert_eq!(&dec("1.5") * dec("-0.2"), dec("-0.3"));
+        assert_eq!(dec("1") / dec("3"), dec("0.(3)"));
+        assert_eq!(dec("0.5") - dec("0.5"), DecimalTuple::zero());
+    }
+
+    #[test]
+    fn test_div_zero() {
+        assert_eq!(dec("1").checked_div(&dec("0")), None);
+        assert_eq!(dec("1").checked_div(&dec("4")), Some(dec("0.25")));
+        let res = std::panic::catch_unwind(|| dec("1") / dec("0.0"));
+        assert!(res.is_err());
+    }
+}
+
+#[cfg(test)]
+mod tests_base {
+    use super::*;
+
+    fn rat(s: &str) -> BigRational { s.parse().unwrap() }
+
+    #[test]
+    fn test_to_base_string() {
+        assert_eq!(to_base_string(&rat("1/3"), 2), "0.(01...)");
+        assert_eq!(to_base_string(&rat("1/10"), 2), "0.0(0011...)");
+        assert_eq!(to_base_string(&rat("255/16"), 16), "f.f");
+        assert_eq!(to_base_string(&rat("-5"), 2), "-101");
+        assert_eq!(to_base_string(&rat("1/3"), 3), "0.1");
+        assert_eq!(to_base_string(&rat("1/7"), 10), "0.(142857...)");
+    }
+
+    #[test]
+    fn test_to_base_string_bounded() {
+        // the period of 1/1000003 in base 2 is 1000002 digits long
+        let r = rat("1/1000003");
+        assert_eq!(to_base_string_bounded(&r, 2, 1000), None);
+        let actual = to_base_string_bounded(&rat("1/10"), 2, 4);
+        assert_eq!(actual.as_deref(), Some("0.0(0011...)"));
+        assert_eq!(to_base_string_bounded(&rat("1/10"), 2, 3), None);
+
+        assert_eq!(to_base_string_truncated(&rat("-1/3"), 2, 4), "-0.0101");
+        assert_eq!(to_base_string_truncated(&rat("5"), 2, 4), "101.0000");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for s in ["1/3", "-1/10", "255/16", "22/7", "0", "-5/6"] {
+            for base in [2, 3, 8, 10, 16, 36] {
+                let r = rat(s);
+                let out = to_base_string(&r, base);
+                assert_eq!(parse_base(&out, base), Some(r), "{out}");
+            }
+        }
+        assert_eq!(parse_base("0.(01)", 2), Some(rat("1/3")));
+        assert_eq!(parse_base("0.(01", 2), None);
+    }
+}
+
+#[cfg(test)]
+mod tests_benchmark {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let test_suite = [
+            ("1/7", Some((0, 6))),
+            ("1/6", Some((1, 1))),
+            ("1/8", Some((3, 0))),
+            // the period of 982451652 digits is not searched for
+            ("1/982451653", None),
+        ];
+        for (rat, expected) in test_suite {
+            let rat = rat.parse().unwrap();
+            let (period, elapsed) = benchmark_decimal(&rat, 1000);
+            assert_eq!(period, expected, "{rat}");
+            assert!(elapsed < Duration::from_secs(1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_sign {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let x: DecimalTuple = "1.2(3)".parse().unwrap();
+        assert_eq!(x.sign(), Plus);
+        let neg = x.clone().with_sign(Minus);
+        assert_eq!(neg, "-1.2(3)".parse().unwrap());
+        assert_eq!(neg.clone().with_sign(Plus), x);
+        assert_eq!(neg.with_sign(NoSign), x);
+
+        let zero: DecimalTuple = "0".parse().unwrap();
+        for sign in [Minus, NoSign, Plus] {
+            assert_eq!(zero.clone().with_sign(sign).sign(), NoSign);
+        }
+    }
+
+    #[test]
+    fn test_signum() {
+        let test_suite = [
+            ("-1.5", (-1, true, false)),
+            ("0.(3)", (1, false, true)),
+            ("0", (0, false, false)),
+            ("-0", (0, false, false)),
+            ("-0.000", (0, false, false)),
+        ];
+        for (s, expected) in test_suite {
+            let x: DecimalTuple = s.parse().unwrap();
+            let actual = (x.signum(), x.is_negative(), x.is_positive());
+            assert_eq!(actual, expected, "{s}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_accessors {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let x: DecimalTuple = "-12.3(45)".parse().unwrap();
+        assert_eq!(x.sign(), Minus);
+        assert_eq!(x.integer_part(), &BigUint::from(12_u32));
+        assert_eq!(x.once_digits(), [3]);
+        assert_eq!(x.rep_digits(), [4, 5]);
+
+        let x: DecimalTuple = "0.250".parse().unwrap();
+        assert_eq!(x.integer_part(), &BigUint::zero());
+        assert_eq!((x.once_digits(), x.rep_digits()), (&[2, 5][..], &[][..]));
+    }
+}
+
+#[cfg(test)]
+mod tests_period {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let test_suite =
+            [("0.(142857)", (0, 6)), ("0.1(6)", (1, 1)), ("0.125", (3, 0))];
+        for (s, expected) in test_suite {
+            let x: DecimalTuple = s.parse().unwrap();
+            assert_eq!((x.preperiod(), x.period()), expected, "{s}");
+        }
+        let x: DecimalTuple = "0.(9)".parse().unwrap();
+        assert!(x.is_integer());
+        assert_eq!((x.preperiod(), x.period()), (0, 0));
+    }
+}
+
+#[cfg(test)]
+mod tests_max_period {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let rat: BigRational = "1/7".parse().unwrap();
+        let dec = DecimalTuple::from_rational(rat.clone(), 6).unwrap();
+        assert_eq!(dec, "0.(142857)".parse().unwrap());
+        assert_eq!(DecimalTuple::from_rational(rat, 5), None);
+
+        // 1/1024 has no period, however long the digits are
+        let rat: BigRational = "1/1024".parse().unwrap();
+        let dec = DecimalTuple::from_rational(rat, 0).unwrap();
+        assert_eq!(dec, "0.0009765625".parse().unwrap());
+
+        // the period of 1/1000003 is 1000002 digits long
+        let rat: BigRational = "1/1000003".parse().unwrap();
+        assert_eq!(DecimalTuple::from_rational(rat, 1000), None);
+        let rat: BigRational = "1/3".parse().unwrap();
+        assert_eq!(DecimalTuple::from_rational(rat, 0), None);
+    }
+}
+
+#[cfg(test)]
+mod tests_canonical {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let test_suite = [
+            ("0.199(9)", "0.2"),
+            ("0.(9)", "1"),
+            ("-0.(9)", "-1"),
+            ("0.(3)", "0.(3...)"),
+            ("0.1(21)", "0.(12...)"),
+            ("0.(1212)", "0.(12...)"),
+            ("1.2500", "1.25"),
+            ("007.5", "7.5"),
+            ("-0", "0"),
+        ];
+        for (s, expected) in test_suite {
+            let x: DecimalTuple = s.parse().unwrap();
+            assert!(x.is_canonical(), "{s}");
+            assert_eq!(x.to_string(), expected, "{s}");
+            assert_eq!(x.to_string().parse::<DecimalTuple>().unwrap(), x);
+        }
+
+        let digits = |once: &[u8], rep: &[u8]| DecimalTuple {
+            sign: Plus,
+            int: BigUint::zero(),
+            frac_once: once.to_vec(),
+            frac_rep: rep.to_vec(),
+        };
+        assert!(digits(&[1], &[6]).is_canonical());
+        for (once, rep) in [
+            (&[1, 9][..], &[9][..]),
+            (&[], &[0]),
+            (&[1], &[2, 1]),
+            (&[], &[1, 2, 1, 2]),
+            (&[5, 0], &[]),
+        ] {
+            assert!(!digits(once, rep).is_canonical(), "{once:?} {rep:?}");
+        }
+        assert!(!digits(&[], &[]).is_canonical());
+        assert!(
+            !DecimalTuple { sign: NoSign, ..digits(&[5], &[]) }.is_canonical()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests_scientific {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let test_suite = [
+            ("1/1000000000000", Some(("1", -12))),
+            ("-3/20000000", Some(("-1.5", -7))),
+            ("1/3000", Some(("3.(3...)", -4))),
+            ("123456789", Some(("1.23456789", 8))),
+            ("7", Some(("7", 0))),
+            ("999/1000", Some(("9.99", -1))),
+            ("0", None),
+        ];
+        for (s, expected) in test_suite {
+            let rat: BigRational = s.parse().unwrap();
+            let actual = to_scientific(&rat, usize::MAX)
+                .map(|(m, e)| (m.to_string(), e));
+            let expected = expected.map(|(m, e)| (m.to_owned(), e));
+            assert_eq!(actual, expected, "{s}");
+        }
+
+        let rat: BigRational = "1/7000000".parse().unwrap();
+        assert!(to_scientific(&rat, 5).is_none());
+    }
 }
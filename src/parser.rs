@@ -1,18 +1,62 @@
 use combine::{
-    attempt, chainl1, choice, eof,
-    error::Format,
-    many1, optional, parser,
+    attempt, chainl1, choice,
+    easy::{self, Errors},
+    eof,
+    error::{Format, StreamError},
+    look_ahead, many, many1, not_followed_by, optional, parser,
     parser::{
-        char::{char, digit, spaces},
-        choice::ChoiceParser,
-        token::Token,
+        char::{char, digit, spaces, string},
+        token::{one_of, satisfy},
     },
-    position,
-    stream::PointerOffset,
-    unexpected_any, value, Parser, Stream, StreamOnce,
+    position, sep_by1,
+    stream::{PointerOffset, StreamErrorFor},
+    unexpected_any, value, EasyParser, Parser, Stream, StreamOnce,
 };
 
-use crate::ast::{Expr, LitComponent};
+use std::ops::Range;
+
+use num_bigint::BigInt;
+
+use crate::{
+    ast::{ConstKind, Expr, FnKind, LitComponent, FUNCTIONS},
+    constants::DEFAULT_MAX_DEPTH,
+};
+
+/// Digits with single underscores in between, e.g. `1_000`, without the
+/// underscores.
+fn digits<Input>(
+    digit: impl Parser<Input, Output = char>,
+) -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
+{
+    sep_by1(many1(digit), char('_')).map(|groups: Vec<String>| groups.concat())
+}
+
+/// `0x1F`, `0b1010`, or `0o17`, as integers. Neither the fractional part nor
+/// the exponent is allowed, so `0b1e3` is an error rather than `0b1 * 1000`.
+fn parse_radix_literal<Input>() -> impl Parser<Input, Output = LitComponent>
+where
+    Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
+{
+    let radix = choice([char('x'), char('o'), char('b')]).map(|c| match c {
+        'x' => 16,
+        'o' => 8,
+        _ => 2,
+    });
+    attempt((optional(char('-')), char('0'), radix))
+        .then(|(sign, _, radix)| {
+            digits(satisfy(move |c: char| c.is_digit(radix)))
+                .map(move |digits: String| (sign, radix, digits))
+        })
+        .skip(not_followed_by(satisfy(|c: char| {
+            c.is_ascii_alphanumeric() || c == '.'
+        })))
+        .map(|(sign, radix, digits)| {
+            let int = BigInt::parse_bytes(digits.as_bytes(), radix).unwrap();
+            LitComponent::new(format!("{}{int}", sign.unwrap_or('+')), 0)
+        })
+}
 
 fn parse_literal_<Input>() -> impl Parser<Input, Output = LitComponent>
 where
@@ -20,13 +64,13 @@ where
 {
     let tok = (
         optional(char('-')),
-        many1(digit()),
-        optional((char('.'), many1(digit()))),
+        digits(digit()),
+        optional((char('.'), digits(digit()))),
         optional(
             choice([char('E'), char('e')])
                 .with((
                     optional(choice([char('+'), char('-')])),
-                    many1(digit()),
+                    digits(digit()),
                 ))
                 .then(|(sgn, exp): (Option<_>, String)| {
                     match format!("{}{exp}", sgn.unwrap_or('+')).parse::<i32>()
@@ -37,82 +81,283 @@ where
                 }),
         ),
     );
-    tok.map(|(sign, int, frac, exp): (_, String, Option<(char, String)>, _)| {
-        let mut digits = format!("{}{int}", sign.unwrap_or('+'));
-        if let Some((_, frac)) = frac {
-            digits += ".";
-            digits.extend(frac.chars());
-        }
-        LitComponent::new(digits, exp.unwrap_or(0))
-    })
+    let decimal = tok.map(
+        |(sign, int, frac, exp): (_, String, Option<(char, String)>, _)| {
+            let mut digits = format!("{}{int}", sign.unwrap_or('+'));
+            if let Some((_, frac)) = frac {
+                digits += ".";
+                digits.push_str(&frac);
+            }
+            LitComponent::new(digits, exp.unwrap_or(0))
+        },
+    );
+
+    // `50%` is a percent only if no operand follows, so `50 % 3`, `50%3`,
+    // `50%x`, and `50% -3` are the remainders while `50% - 3` and `50% * 3`
+    // are not.
+    let operand = spaces().with(choice((
+        digit(),
+        char('('),
+        satisfy(|c: char| c.is_ascii_alphabetic() || c == '_'),
+        attempt(
+            char('-').skip(look_ahead(satisfy(|c: char| !c.is_whitespace()))),
+        ),
+    )));
+    let percent = attempt(char('%').skip(not_followed_by(attempt(operand))));
+    (parse_radix_literal().or(decimal), optional(percent)).then(
+        |(lit, percent): (LitComponent, _)| match percent {
+            None => value(lit).left(),
+            Some(_) => match lit.percent() {
+                Some(lit) => value(lit).left(),
+                None => unexpected_any("exponent out of range").right(),
+            },
+        },
+    )
+}
+
+/// How the operands group around the operators of the same precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+/// An operator of the grammar. The higher `precedence` binds tighter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Operator {
+    pub symbol: char,
+    pub arity: usize,
+    pub precedence: u8,
+    pub assoc: Assoc,
+}
+
+const fn operator(
+    symbol: char,
+    arity: usize,
+    precedence: u8,
+    assoc: Assoc,
+) -> Operator {
+    Operator { symbol, arity, precedence, assoc }
+}
+
+/// The precedence levels, from the loosest, each of which is a layer of the
+/// parser: `parse_expr_`, `parse_term_`, `parse_unary_`, `parse_implicit_`,
+/// and `parse_postfix_`.
+pub const PREC_ADD: u8 = 1;
+pub const PREC_MUL: u8 = 2;
+pub const PREC_NEG: u8 = 3;
+/// The implicit multiplication `2(3)`, which has no symbol.
+pub const PREC_IMPLICIT: u8 = 4;
+pub const PREC_POSTFIX: u8 = 5;
+
+/// All the operators, which the parser is built from.
+pub const OPERATORS: &[Operator] = &[
+    operator('+', 2, PREC_ADD, Assoc::Left),
+    operator('-', 2, PREC_ADD, Assoc::Left),
+    operator('*', 2, PREC_MUL, Assoc::Left),
+    operator('/', 2, PREC_MUL, Assoc::Left),
+    operator('%', 2, PREC_MUL, Assoc::Left),
+    operator('-', 1, PREC_NEG, Assoc::Right),
+    operator('!', 1, PREC_POSTFIX, Assoc::Left),
+];
+
+/// The symbol of the unary operator at `precedence`.
+fn unary_symbol(precedence: u8) -> char {
+    let mut it = OPERATORS.iter();
+    it.find(|op| op.arity == 1 && op.precedence == precedence).unwrap().symbol
+}
+
+/// Whether `c` is the symbol of a binary operator.
+fn is_binary_op(c: char) -> bool {
+    OPERATORS.iter().any(|op| op.arity == 2 && op.symbol == c)
 }
 
-fn op<Input, const N: usize>(
-    s: [char; N],
+/// One of the binary operators at `precedence`, with the spaces around.
+fn op<Input>(
+    precedence: u8,
 ) -> impl Parser<Input, Output = (PointerOffset<str>, char, PointerOffset<str>)>
 where
     Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
-    [Token<Input>; N]: ChoiceParser<Input, Output = char>,
 {
-    let choices: Vec<_> = s.iter().copied().map(char).collect();
-    let choices: [_; N] = choices.try_into().ok().unwrap();
+    let symbols: Vec<_> = OPERATORS
+        .iter()
+        .filter(|op| op.arity == 2 && op.precedence == precedence)
+        .map(|op| op.symbol)
+        .collect();
     attempt(
-        spaces().with((position(), choice(choices), position())).skip(spaces()),
+        spaces().with((position(), one_of(symbols), position())).skip(spaces()),
     )
 }
 
-fn parse_expr_<Input>() -> impl Parser<Input, Output = Expr>
+/// The depth left inside one more level of nesting, or an error if none is
+/// left.
+fn deeper<Input>(depth: usize) -> impl Parser<Input, Output = usize>
+where
+    Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
+{
+    value(()).and_then(move |_| {
+        depth.checked_sub(1).ok_or_else(|| {
+            StreamErrorFor::<Input>::message_static_message("nested too deeply")
+        })
+    })
+}
+
+fn parse_expr_<Input>(depth: usize) -> impl Parser<Input, Output = Expr>
 where
     Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
 {
-    let tok = op(['+', '-']).map(|(pos_l, op, pos_r)| {
+    let tok = op(PREC_ADD).map(|(pos_l, op, pos_r)| {
         move |l, r| match op {
             '+' => Expr::Add(Box::new(l), Box::new(r), pos_l..pos_r),
             '-' => Expr::Sub(Box::new(l), Box::new(r), pos_l..pos_r),
             _ => unreachable!(),
         }
     });
-    chainl1(parse_term(), tok)
+    chainl1(parse_term(depth), tok)
 }
 
-fn parse_term_<Input>() -> impl Parser<Input, Output = Expr>
+fn parse_term_<Input>(depth: usize) -> impl Parser<Input, Output = Expr>
 where
     Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
 {
-    let tok = op(['*', '/']).map(|(pos_l, op, pos_r)| {
+    let tok = op(PREC_MUL).map(|(pos_l, op, pos_r)| {
         move |l, r| match op {
             '*' => Expr::Mul(Box::new(l), Box::new(r), pos_l..pos_r),
             '/' => Expr::Div(Box::new(l), Box::new(r), pos_l..pos_r),
+            '%' => Expr::Rem(Box::new(l), Box::new(r), pos_l..pos_r),
             _ => unreachable!(),
         }
     });
-    chainl1(parse_factor(), tok)
+    chainl1(parse_unary(depth), tok)
+}
+
+fn parse_unary_<Input>(depth: usize) -> impl Parser<Input, Output = Expr>
+where
+    Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
+{
+    // `-2` and `-(2)` are handled by `parse_factor`; this is for the others
+    // such as `- 2` or `--2`.
+    let neg = (
+        position(),
+        (char(unary_symbol(PREC_NEG)), spaces())
+            .with(deeper(depth))
+            .then(parse_unary),
+        position(),
+    )
+        .map(|(pos_l, x, pos_r)| Expr::Neg(Box::new(x), pos_l..pos_r));
+    attempt(parse_implicit(depth)).or(neg)
+}
+
+fn parse_implicit_<Input>(depth: usize) -> impl Parser<Input, Output = Expr>
+where
+    Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
+{
+    // `2(3)` or `(2) (3)`, and `(2)3` but not `2 3`. This binds tighter than
+    // `*` and `/`, so `6/2(3)` is `1`.
+    let gap = attempt(
+        (position().skip(spaces()), position()).skip(look_ahead(char('('))),
+    )
+    .or((position(), position()).skip(look_ahead(digit())));
+    (parse_postfix(depth), many((gap, parse_postfix(depth)))).map(
+        |(first, rest): (_, Vec<_>)| {
+            rest.into_iter().fold(first, |l, ((pos_l, pos_r), r)| {
+                Expr::Mul(Box::new(l), Box::new(r), pos_l..pos_r)
+            })
+        },
+    )
+}
+
+fn parse_postfix_<Input>(depth: usize) -> impl Parser<Input, Output = Expr>
+where
+    Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
+{
+    let bang = char(unary_symbol(PREC_POSTFIX));
+    let bang = attempt(spaces().with((position(), bang, position())));
+    (parse_factor(depth), many(bang)).map(|(first, rest): (_, Vec<_>)| {
+        rest.into_iter().fold(first, |x, (pos_l, _, pos_r)| {
+            Expr::Factorial(Box::new(x), pos_l..pos_r)
+        })
+    })
 }
 
-fn parse_factor_<Input>() -> impl Parser<Input, Output = Expr>
+fn parse_factor_<Input>(depth: usize) -> impl Parser<Input, Output = Expr>
 where
     Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
 {
     let literal = (position(), parse_literal(), position())
-        .map(|(pos_l, lit, pos_r)| (Expr::Literal(lit, pos_l..pos_r)));
+        .map(|(pos_l, lit, pos_r)| Expr::Literal(lit, pos_l..pos_r));
+    let fn_name = choice(
+        FUNCTIONS
+            .map(|info| attempt(string(info.name)).map(move |_| info.kind)),
+    );
+    let args = move |depth| {
+        let comma = (spaces(), char(','), spaces());
+        spaces().with(sep_by1(parse_expr(depth), attempt(comma)))
+    };
+    let call = (
+        position(),
+        attempt(fn_name.skip((spaces(), char('(')))),
+        deeper(depth).then(args),
+        (spaces(), char(')')).with(position()),
+    )
+        .and_then(|(pos_l, kind, args, pos_r): (_, FnKind, Vec<_>, _)| {
+            if kind.takes(args.len()) {
+                Ok(Expr::Call(kind, args, pos_l..pos_r))
+            } else {
+                Err(StreamErrorFor::<Input>::message_static_message(
+                    "wrong number of arguments",
+                ))
+            }
+        });
+    let name = choice((
+        attempt(string("pi")).map(|_| ConstKind::Pi),
+        char('e').map(|_| ConstKind::E),
+    ))
+    .skip(not_followed_by(satisfy(|c: char| c.is_ascii_alphanumeric())));
+    let constant = (position(), name, position())
+        .map(|(pos_l, kind, pos_r)| Expr::Const(kind, pos_l..pos_r));
+    let var = (position(), identifier(), position())
+        .map(|(pos_l, name, pos_r)| Expr::Var(name, None, pos_l..pos_r));
     let parens = (
         position(),
-        (char('('), spaces()).with(parse_expr()).skip((spaces(), char(')'))),
+        (char('('), spaces())
+            .with(deeper(depth))
+            .then(parse_expr)
+            .skip((spaces(), char(')'))),
         position(),
     )
         .map(|(pos_l, x, pos_r)| Expr::Paren(Box::new(x), pos_l..pos_r));
     let neg_parens = (
         position(),
         (char('-'), spaces(), char('('), spaces())
-            .with(parse_expr())
+            .with(deeper(depth))
+            .then(parse_expr)
             .skip((spaces(), char(')'))),
         position(),
     )
         .map(|(pos_l, x, pos_r)| Expr::NegParen(Box::new(x), pos_l..pos_r));
 
-    attempt(literal).or(parens).or(neg_parens)
+    attempt(literal)
+        .or(call)
+        .or(attempt(constant))
+        .or(var)
+        .or(parens)
+        .or(neg_parens)
 }
 
+/// A name of a variable, e.g. `x` or `ans`.
+fn identifier<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>,
+{
+    let head = satisfy(|c: char| c.is_ascii_alphabetic() || c == '_');
+    let tail = many(satisfy(|c: char| c.is_ascii_alphanumeric() || c == '_'));
+    (head, tail).map(|(c, rest): (_, String)| format!("{c}{rest}"))
+}
+
+/// The names that cannot be assigned to, along with the functions.
+const RESERVED: &[&str] = &["pi", "e"];
+
 parser! {
     fn parse_literal[Input]()(Input) -> LitComponent
     where
@@ -123,29 +368,56 @@ parser! {
 }
 
 parser! {
-    fn parse_expr[Input]()(Input) -> Expr
+    fn parse_expr[Input](depth: usize)(Input) -> Expr
+    where
+        [Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>]
+    {
+        parse_expr_(*depth)
+    }
+}
+
+parser! {
+    fn parse_term[Input](depth: usize)(Input) -> Expr
+    where
+        [Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>]
+    {
+        parse_term_(*depth)
+    }
+}
+
+parser! {
+    fn parse_unary[Input](depth: usize)(Input) -> Expr
+    where
+        [Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>]
+    {
+        parse_unary_(*depth)
+    }
+}
+
+parser! {
+    fn parse_implicit[Input](depth: usize)(Input) -> Expr
     where
         [Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>]
     {
-        parse_expr_()
+        parse_implicit_(*depth)
     }
 }
 
 parser! {
-    fn parse_term[Input]()(Input) -> Expr
+    fn parse_postfix[Input](depth: usize)(Input) -> Expr
     where
         [Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>]
     {
-        parse_term_()
+        parse_postfix_(*depth)
     }
 }
 
 parser! {
-    fn parse_factor[Input]()(Input) -> Expr
+    fn parse_factor[Input](depth: usize)(Input) -> Expr
     where
         [Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>]
     {
-        parse_factor_()
+        parse_factor_(*depth)
     }
 }
 
@@ -154,15 +426,184 @@ parser! {
     where
         [Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>]
     {
-        spaces().with(parse_expr()).skip((spaces(), eof()))
+        parse_line_bounded(DEFAULT_MAX_DEPTH)
+    }
+}
+
+parser! {
+    /// Same as `parse_line`, but nested at most `max_depth` deep.
+    pub fn parse_line_bounded[Input](max_depth: usize)(Input) -> Expr
+    where
+        [Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>]
+    {
+        spaces().with(parse_expr(*max_depth)).skip((spaces(), eof()))
+    }
+}
+
+parser! {
+    fn parse_statement[Input](max_depth: usize)(Input) -> (Option<(String, PointerOffset<str>)>, Expr)
+    where
+        [Input: Stream<Token = char> + StreamOnce<Position = PointerOffset<str>>]
+    {
+        let lhs = (position(), identifier())
+            .skip((spaces(), char('='), not_followed_by(char('='))));
+        (
+            spaces().with(optional(attempt(lhs))),
+            spaces().with(parse_expr(*max_depth)).skip((spaces(), eof())),
+        )
+            .map(|(lhs, expr)| (lhs.map(|(pos, name)| (name, pos)), expr))
+    }
+}
+
+/// Parses the line, with the errors positioned at byte offsets. If
+/// `strict`, a literal with a redundant leading zero such as `007` or
+/// `-01.5` is also an error. The parentheses, the calls, and the unary
+/// minuses nested deeper than `max_depth` are errors as well, rather than
+/// overflowing the stack.
+pub fn parse_line_checked(
+    s: &str,
+    strict: bool,
+    max_depth: usize,
+) -> Result<Expr, Errors<char, String, usize>> {
+    let (expr, _) = parse_line_bounded(max_depth)
+        .easy_parse(s)
+        .map_err(|e| translate_errors(e, s))?;
+    check_literals(&expr, s, strict)?;
+    Ok(expr)
+}
+
+/// A statement of the line, with the assigned name if any.
+pub type Statement = (Option<String>, Expr);
+
+/// Parses the line, either an expression or an assignment `name = expr`,
+/// with the name if any.
+pub fn parse_statement_checked(
+    s: &str,
+    strict: bool,
+    max_depth: usize,
+) -> Result<Statement, Errors<char, String, usize>> {
+    parse_statement_in(s, 0..s.len(), strict, max_depth)
+}
+
+/// Parses the `;`-separated statements of the line, e.g. `x = 1/3; x * 3`.
+/// A trailing `;` is allowed. A parse error is confined to its statement,
+/// and the parsing resumes after the next `;`.
+pub fn parse_program_checked(
+    s: &str,
+    strict: bool,
+    max_depth: usize,
+) -> Vec<Result<Statement, Errors<char, String, usize>>> {
+    statement_ranges(s)
+        .into_iter()
+        .map(|range| parse_statement_in(s, range, strict, max_depth))
+        .collect()
+}
+
+/// The ranges of the `;`-separated statements, without the empty one after
+/// the trailing `;`.
+pub fn statement_ranges(s: &str) -> Vec<Range<usize>> {
+    let mut res = vec![];
+    let mut start = 0;
+    for (i, _) in s.match_indices(';') {
+        res.push(start..i);
+        start = i + 1;
+    }
+    if res.is_empty() || !s[start..].trim().is_empty() {
+        res.push(start..s.len());
+    }
+    res
+}
+
+/// Parses `s[range]` as a statement, with the errors positioned in `s`.
+fn parse_statement_in(
+    s: &str,
+    range: Range<usize>,
+    strict: bool,
+    max_depth: usize,
+) -> Result<Statement, Errors<char, String, usize>> {
+    let ((lhs, expr), _) = parse_statement(max_depth)
+        .easy_parse(&s[range])
+        .map_err(|e| translate_errors(e, s))?;
+    let name = match lhs {
+        Some((name, pos))
+            if RESERVED.contains(&name.as_str())
+                || FUNCTIONS.iter().any(|info| info.name == name) =>
+        {
+            let mut err = Errors::empty(pos.translate_position(s));
+            err.add_error(easy::Error::Message(easy::Info::Static(
+                "cannot assign to a reserved name",
+            )));
+            return Err(err);
+        }
+        lhs => lhs.map(|(name, _)| name),
+    };
+    check_literals(&expr, s, strict)?;
+    Ok((name, expr))
+}
+
+/// Rejects a literal with a redundant leading zero if `strict`.
+fn check_literals(
+    expr: &Expr,
+    s: &str,
+    strict: bool,
+) -> Result<(), Errors<char, String, usize>> {
+    if strict {
+        for range in expr.literal_ranges(s) {
+            let lit = &s[range.clone()];
+            let int = lit.trim_start_matches('-');
+            let int_len = int
+                .find(|c: char| !c.is_ascii_digit() && c != '_')
+                .unwrap_or(int.len());
+            if int_len > 1 && int.starts_with('0') {
+                let pos = range.start + lit.len() - int.len();
+                let mut err = Errors::empty(pos);
+                err.add_error(easy::Error::Message(easy::Info::Static(
+                    "leading zeros are not allowed",
+                )));
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Converts the errors into the owned ones positioned at byte offsets.
+///
+/// A binary operator followed by another operator, e.g. `1 ++ 2`, is
+/// reported as such at the second operator rather than as the list of the
+/// expected tokens.
+pub fn translate_errors(
+    err: Errors<char, &str, PointerOffset<str>>,
+    s: &str,
+) -> Errors<char, String, usize> {
+    let mut err = err
+        .map_position(|pos| pos.translate_position(s))
+        .map_range(|r| r.to_owned());
+    let pos = err.position;
+    let at_op = s[pos..].starts_with(is_binary_op);
+    let after_op = s[..pos].trim_end().ends_with(is_binary_op);
+    if at_op && after_op {
+        err.errors = vec![easy::Error::Message(easy::Info::Static(
+            "unexpected operator after operator",
+        ))];
     }
+    err
 }
 
 #[cfg(test)]
 mod tests {
-    use combine::EasyParser;
+    use num::{Signed, Zero};
+    use num_rational::BigRational;
 
     use super::*;
+    use crate::ast::{
+        walk, EvalContext, EvalError, EvalOptions, ExprTy, NodeCounter,
+        Visitor, Warning, WarningKind,
+    };
+    use crate::constants::{MAX_FACTORIAL, MAX_FACTORIAL_ERROR, PI_DIGITS};
+    use crate::number::DecimalTuple;
+    use crate::test_util::assert_evaluates_to;
+    use crate::utils::translate_range;
 
     #[test]
     fn test() {
@@ -175,4 +616,802 @@ mod tests {
             Some((("3/5".parse().unwrap(), 0.6), 0..s.len()))
         );
     }
+
+    #[test]
+    fn test_consecutive_operators() {
+        let msg = || {
+            easy::Error::Message(easy::Info::Static(
+                "unexpected operator after operator",
+            ))
+        };
+        for (s, pos) in [("1 ++ 2", 3), ("3 */ 4", 3), ("1 + * 2", 4)] {
+            let err = parse_line().easy_parse(s).unwrap_err();
+            let err = translate_errors(err, s);
+            assert_eq!(err.position, pos);
+            assert_eq!(err.errors, vec![msg()]);
+        }
+
+        let s = "1 + ";
+        let err = translate_errors(parse_line().easy_parse(s).unwrap_err(), s);
+        assert!(!err.errors.contains(&msg()));
+    }
+
+    #[test]
+    fn test_eval_f32() {
+        let test_suite = [
+            ("0.1 + 0.2", 0.1_f32 + 0.2_f32),
+            ("1/3", 1.0 / 3.0),
+            ("16777217", 16777216.0),
+            ("7 % -3", -2.0),
+            ("-(3!)", -6.0),
+            ("35!", f32::INFINITY),
+        ];
+        for (s, expected) in test_suite {
+            let expr = parse_line().easy_parse(s).unwrap().0;
+            let (res, _) = expr.eval_with_f32(s, &Default::default());
+            assert_eq!(res.unwrap().2, expected, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_visitor() {
+        let test_suite = [
+            ("1", 1),
+            ("1 + 2", 3),
+            ("1 + 2 * (3)", 6),
+            ("-(1 - 2)!", 5),
+            ("2(3)(4)", 7),
+            ("-1 % 3!", 4),
+        ];
+        for (s, expected) in test_suite {
+            let expr = parse_line().easy_parse(s).unwrap().0;
+            let mut counter = NodeCounter::default();
+            counter.visit(&expr);
+            assert_eq!(counter.count, expected, "{s}");
+        }
+
+        // overriding a single kind of the nodes
+        struct Binary(Vec<usize>);
+        impl Visitor for Binary {
+            fn visit_binary(&mut self, expr: &Expr, lhs: &Expr, rhs: &Expr) {
+                let count = |e| {
+                    let mut c = NodeCounter::default();
+                    c.visit(e);
+                    c.count
+                };
+                self.0.push(count(lhs) + count(rhs));
+                walk(self, expr);
+            }
+        }
+        let expr = parse_line().easy_parse("(1 + 2) * 3 - 4").unwrap().0;
+        let mut binary = Binary(vec![]);
+        binary.visit(&expr);
+        assert_eq!(binary.0, [7, 5, 2]);
+    }
+
+    #[test]
+    fn test_strict_literals() {
+        for s in ["007", "1 + 00", "-01.5", "2 * (010)"] {
+            assert!(parse_line_checked(s, false, DEFAULT_MAX_DEPTH).is_ok());
+            assert!(
+                parse_line_checked(s, true, DEFAULT_MAX_DEPTH).is_err(),
+                "{s}"
+            );
+        }
+        for s in ["0", "0.07", "-0.5e3", "10 + 0.0", "0e5"] {
+            assert!(
+                parse_line_checked(s, true, DEFAULT_MAX_DEPTH).is_ok(),
+                "{s}"
+            );
+        }
+        let err =
+            parse_line_checked("1 + 007", true, DEFAULT_MAX_DEPTH).unwrap_err();
+        assert_eq!(err.position, 4);
+        let err =
+            parse_line_checked("2 - -01", true, DEFAULT_MAX_DEPTH).unwrap_err();
+        assert_eq!(err.position, 5);
+    }
+
+    #[test]
+    fn test_radix_literals() {
+        let test_suite = [
+            ("0x1F", "31"),
+            ("0b1010", "10"),
+            ("0o17", "15"),
+            ("-0xff", "-255"),
+            ("0xFF / 16", "255/16"),
+            ("0b11 * (1 + 0o7)", "24"),
+            ("2(0x10)", "32"),
+            ("0x1E", "30"),
+        ];
+        for (s, expected) in test_suite {
+            let expr = parse_line_checked(s, true, DEFAULT_MAX_DEPTH).unwrap();
+            let (rat, _) = expr.eval(s, &Default::default(), 0).unwrap().0;
+            assert_eq!(rat, expected.parse().unwrap(), "{s}");
+        }
+
+        for (s, pos) in [("0xG", 2), ("0b12", 3), ("0o7.5", 3), ("0b1e3", 3)] {
+            let err =
+                parse_line_checked(s, false, DEFAULT_MAX_DEPTH).unwrap_err();
+            assert_eq!(err.position, pos, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        let test_suite = [
+            ("1_0", "10"),
+            ("1_000_000.000_001", "1000000000001/1000000"),
+            ("1e1_0", "10000000000"),
+            ("0xff_ff", "65535"),
+        ];
+        for (s, expected) in test_suite {
+            let expr = parse_line_checked(s, true, DEFAULT_MAX_DEPTH).unwrap();
+            let (rat, _) = expr.eval(s, &Default::default(), 0).unwrap().0;
+            assert_eq!(rat, expected.parse().unwrap(), "{s}");
+        }
+
+        for s in ["1__0", "1_", "1_.5", "1._5", "1e_1", "1_e1"] {
+            assert!(
+                parse_line_checked(s, false, DEFAULT_MAX_DEPTH).is_err(),
+                "{s}"
+            );
+        }
+        let expr = parse_line_checked("_1", false, DEFAULT_MAX_DEPTH).unwrap();
+        assert!(matches!(expr, Expr::Var(..)));
+        assert!(parse_line_checked("0_7", true, DEFAULT_MAX_DEPTH).is_err());
+    }
+
+    #[test]
+    fn test_percent() {
+        let test_suite = [
+            ("50%", "1/2"),
+            ("15% * 200", "30"),
+            ("100% - 15%", "17/20"),
+            ("1e-1%", "1/1000"),
+            ("-0x10%", "-4/25"),
+            ("50 % 3", "2"),
+            ("50%3", "2"),
+            ("50% -3", "-1"),
+            ("7%(4)", "3"),
+            ("7%sqrt(4)", "1"),
+        ];
+        for (s, expected) in test_suite {
+            let expr = parse_line_checked(s, true, DEFAULT_MAX_DEPTH).unwrap();
+            let (rat, _) = expr.eval(s, &Default::default(), 0).unwrap().0;
+            assert_eq!(rat, expected.parse().unwrap(), "{s}");
+        }
+
+        for s in ["7%pi", "50%x", "50% x", "7%_1"] {
+            let expr = parse_line_checked(s, true, DEFAULT_MAX_DEPTH).unwrap();
+            assert!(matches!(expr, Expr::Rem(..)), "{s}");
+        }
+
+        let s = "50% + 1";
+        let expr = parse_line_checked(s, true, DEFAULT_MAX_DEPTH).unwrap();
+        assert_eq!(expr.literal_ranges(s), [0..3, 6..7]);
+    }
+
+    #[test]
+    fn test_zero_operand() {
+        let s = "0 * (1/3)";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let ((rat, flt), _) = actual.eval(s, &Default::default(), 0).unwrap();
+        assert_eq!((rat, flt), (BigRational::zero(), 0.0));
+
+        let s = "0 * 1e400";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let ((rat, flt), _) = actual.eval(s, &Default::default(), 0).unwrap();
+        assert!(rat.is_zero());
+        assert!(flt.is_nan());
+
+        let s = "0 - 1/3 + 0";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let ((rat, _), _) = actual.eval(s, &Default::default(), 0).unwrap();
+        assert_eq!(rat, "-1/3".parse().unwrap());
+    }
+
+    #[test]
+    fn test_rem() {
+        let test_suite = [
+            ("7 % 3", "1", 1.0),
+            ("-1 % 3", "2", 2.0),
+            ("7 % -3", "-2", -2.0),
+            ("7.5 % 2", "3/2", 1.5),
+            ("2 * 7 % 4", "2", 2.0),
+        ];
+        for (s, rat, flt) in test_suite {
+            assert_evaluates_to(s, rat, flt);
+        }
+
+        let s = "1 % (1 - 1)";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        assert!(actual.eval(s, &Default::default(), 0).is_err());
+    }
+
+    #[test]
+    fn test_implicit_mul() {
+        let test_suite = [
+            ("2(3+4)", "14"),
+            ("(1+2)(3+4)", "21"),
+            ("-2(3)", "-6"),
+            ("(1)(2)(3)", "6"),
+            ("(2)3", "6"),
+            ("2 (3)", "6"),
+            ("(2)-3", "-1"),
+            ("1-(2)", "-1"),
+            ("6/2(3)", "1"),
+        ];
+        for (s, rat) in test_suite {
+            let actual = parse_line().easy_parse(s).unwrap().0;
+            let actual = actual.eval(s, &Default::default(), 0).unwrap();
+            assert_eq!(actual.0.0, rat.parse().unwrap(), "{s}");
+            assert_eq!(actual.1, 0..s.len());
+        }
+
+        assert!(parse_line().easy_parse("2 3").is_err());
+        assert!(parse_line().easy_parse("(2) 3").is_err());
+    }
+
+    #[test]
+    fn test_implicit_mul_range() {
+        let s = "2 (3)";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        match actual {
+            Expr::Mul(_, _, range) => {
+                assert_eq!(translate_range(&range, s), 1..2);
+            }
+            _ => panic!("expected `Expr::Mul`: {actual:?}"),
+        }
+    }
+
+    #[test]
+    fn test_neg() {
+        let test_suite = [
+            ("-3*-4", "12", 12.0),
+            ("-(2)*-5", "10", 10.0),
+            ("- 2", "-2", -2.0),
+            ("--2", "2", 2.0),
+            ("- -(3)", "3", 3.0),
+            ("2 * - 3", "-6", -6.0),
+            ("1 - -2", "3", 3.0),
+            ("- 2(3)", "-6", -6.0),
+        ];
+        for (s, rat, flt) in test_suite {
+            assert_evaluates_to(s, rat, flt);
+        }
+
+        let s = "- 0";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let actual = actual.eval(s, &Default::default(), 0).unwrap();
+        assert!(actual.0.1.is_sign_negative());
+
+        // estimated under `neg` rather than `par`
+        let test_suite = [
+            ("- 2", ExprTy::Neg),
+            ("-(2)", ExprTy::Paren),
+            ("-2", ExprTy::Literal),
+        ];
+        for (s, expected) in test_suite {
+            let actual = parse_line().easy_parse(s).unwrap().0;
+            assert_eq!(actual.expr_ty(), expected, "{s}");
+        }
+        let ctx = EvalContext::new(ExprTy::Neg, 1);
+        let opts = EvalOptions::new().with_estimate(vec!["+par".into()]);
+        assert!(!opts.do_estimate(&ctx));
+        let opts = EvalOptions::new().with_estimate(vec!["+neg".into()]);
+        assert!(opts.do_estimate(&ctx));
+    }
+
+    #[test]
+    fn test_estimate_depth() {
+        // in `1 + 2 * 3`, the sum is at depth 0, `1` and `2 * 3` at depth 1,
+        // and `2` and `3` at depth 2
+        let each = vec!["+each".into()];
+        let opts =
+            EvalOptions::new().with_estimate(each).with_estimate_depth(Some(1));
+        let tys = [
+            ExprTy::Literal,
+            ExprTy::Binary,
+            ExprTy::Paren,
+            ExprTy::Neg,
+            ExprTy::Call,
+        ];
+        for ty in tys {
+            assert!(opts.do_estimate(&EvalContext::new(ty, 0)), "{ty:?}");
+            assert!(opts.do_estimate(&EvalContext::new(ty, 1)), "{ty:?}");
+            assert!(!opts.do_estimate(&EvalContext::new(ty, 2)), "{ty:?}");
+        }
+
+        // the whole expression regardless of the flags and the depth
+        for depth in [None, Some(0), Some(1)] {
+            let opts = EvalOptions::new().with_estimate_depth(depth);
+            for ty in tys {
+                assert!(opts.do_estimate(&EvalContext::new(ty, 0)), "{ty:?}");
+                assert!(!opts.do_estimate(&EvalContext::new(ty, 1)), "{ty:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_factorial() {
+        let test_suite = [
+            ("3!", "6"),
+            ("3!!", "720"),
+            ("0!", "1"),
+            ("(1 + 2)! / 2", "3"),
+            ("2(3)!", "12"),
+            ("- 3!", "-6"),
+            ("21!", "51090942171709440000"),
+        ];
+        for (s, rat) in test_suite {
+            let actual = parse_line().easy_parse(s).unwrap().0;
+            let actual = actual.eval(s, &Default::default(), 0).unwrap();
+            assert_eq!(actual.0.0, rat.parse().unwrap(), "{s}");
+            assert_eq!(actual.1, 0..s.len());
+        }
+
+        // 22! is the largest factorial exactly representable in `f64`.
+        let s = "22!";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let ((rat, flt), _) = actual.eval(s, &Default::default(), 0).unwrap();
+        assert_eq!(BigRational::from_float(flt), Some(rat));
+        let s = "23!";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let ((rat, flt), _) = actual.eval(s, &Default::default(), 0).unwrap();
+        assert_ne!(BigRational::from_float(flt), Some(rat));
+
+        for s in ["(0 - 1)!", "(1/2)!"] {
+            let actual = parse_line().easy_parse(s).unwrap().0;
+            let actual = actual.eval(s, &Default::default(), 0);
+            assert!(matches!(actual, Err(EvalError::DomainError(..))), "{s}");
+        }
+
+        // rejected rather than hanging
+        for s in ["10001!", "100000000!", "1e400!"] {
+            let actual = parse_line().easy_parse(s).unwrap().0;
+            let actual = actual.eval(s, &Default::default(), 0);
+            let (range, msg) = match actual {
+                Err(EvalError::DomainError(range, msg)) => (range, msg),
+                _ => panic!("expected a domain error: {actual:?}"),
+            };
+            assert_eq!(range, 0..s.len());
+            assert_eq!(msg, MAX_FACTORIAL_ERROR);
+        }
+        let limit = format!(" {MAX_FACTORIAL} ");
+        assert!(MAX_FACTORIAL_ERROR.contains(&limit));
+    }
+
+    #[test]
+    fn test_overflow_warning() {
+        let test_suite: [(_, &[_]); 6] = [
+            ("1e300 * 1e300", &[(0..13, 6..7)]),
+            ("1e308 * 10 / 10", &[(0..10, 6..7)]),
+            ("171!", &[(0..4, 3..4)]),
+            (
+                "1e300 * 1e300 - 1e300 * 1e300",
+                &[(0..13, 6..7), (16..29, 22..23)],
+            ),
+            ("1e300(1e300)", &[(0..12, 5..5)]),
+            ("1e300 * 1e8", &[]),
+        ];
+        for (s, spans) in test_suite {
+            let actual = parse_line().easy_parse(s).unwrap().0;
+            let (_, warnings) =
+                actual.eval_with_warnings(s, &Default::default());
+            let expected: Vec<_> = spans
+                .iter()
+                .map(|(span, op)| Warning {
+                    kind: WarningKind::Overflow,
+                    span: span.clone(),
+                    op: Some(op.clone()),
+                })
+                .collect();
+            assert_eq!(warnings, expected, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let test_suite = [
+            ("1.0001 - 1", Some(4)),
+            ("1.001 - 1", Some(3)),
+            ("1 + -0.999", Some(3)),
+            ("1.01 - 1", None),
+            ("1.0001 + 1", None),
+            ("0.1 + 0.2 - 0.3", None),
+        ];
+        let opts = EvalOptions::new().with_estimate(vec!["+cancel".into()]);
+        for (s, expected) in test_suite {
+            let actual = parse_line().easy_parse(s).unwrap().0;
+            let (_, warnings) = actual.eval_with_warnings(s, &opts.silenced());
+            let expected: Vec<_> = expected
+                .map(|digits| Warning {
+                    kind: WarningKind::Cancellation(digits),
+                    span: 0..s.len(),
+                    op: None,
+                })
+                .into_iter()
+                .collect();
+            assert_eq!(warnings, expected, "{s}");
+        }
+
+        let s = "1.0001 - 1";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let (_, warnings) = actual.eval_with_warnings(s, &Default::default());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_exact_despite_inexact() {
+        let test_suite = [
+            ("(1/3) * 3", true),
+            ("0.1 * 10", true),
+            ("1/3", false),
+            ("0.1 + 0.2", false),
+            ("2 * 3", false),
+        ];
+        for (s, expected) in test_suite {
+            let actual = parse_line().easy_parse(s).unwrap().0;
+            let (_, warnings) =
+                actual.eval_with_warnings(s, &Default::default());
+            let note = Warning {
+                kind: WarningKind::ExactDespiteInexact,
+                span: 0..s.len(),
+                op: None,
+            };
+            assert_eq!(warnings.contains(&note), expected, "{s}");
+        }
+        let kind = WarningKind::ExactDespiteInexact;
+        assert_eq!(kind.to_string(), "exact despite inexact intermediates");
+    }
+
+    #[test]
+    fn test_constants() {
+        let s = "pi * 2";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let (res, warnings) = actual.eval_with_warnings(s, &Default::default());
+        let ((rat, flt), _) = res.unwrap();
+        assert_eq!(flt, std::f64::consts::TAU);
+        let pi: DecimalTuple = PI_DIGITS.parse().unwrap();
+        let err = (rat - BigRational::from(pi) * BigInt::from(2)).abs();
+        assert!(err < BigRational::new(1.into(), BigInt::from(10).pow(45)));
+        let kind = WarningKind::ApproximateTruth(ConstKind::Pi);
+        assert_eq!(warnings, [Warning { kind, span: 0..2, op: None }]);
+
+        let s = "-e + 1e1";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let (_, flt) = actual.eval(s, &Default::default(), 0).unwrap().0;
+        assert_eq!(flt, 10.0 - std::f64::consts::E);
+
+        for s in ["pie", "ex"] {
+            let expr = parse_line().easy_parse(s).unwrap().0;
+            assert!(matches!(expr, Expr::Var(..)), "{s}");
+        }
+        assert!(parse_line().easy_parse("2e").is_err());
+    }
+
+    #[test]
+    fn test_sqrt() {
+        assert_evaluates_to("sqrt(9/16)", "3/4", 0.75);
+        assert_evaluates_to("sqrt(4) + 1", "3", 3.0);
+        assert_evaluates_to("sqrt( 0 )", "0", 0.0);
+
+        let s = "sqrt(2)";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let (res, warnings) = actual.eval_with_warnings(s, &Default::default());
+        let ((rat, flt), _) = res.unwrap();
+        assert_eq!(flt, std::f64::consts::SQRT_2);
+        let err = (&rat * &rat - BigRational::from_integer(2.into())).abs();
+        assert!(err < BigRational::new(1.into(), BigInt::from(10).pow(49)));
+        let kind = WarningKind::IrrationalRoot;
+        assert_eq!(warnings, [Warning { kind, span: 0..7, op: None }]);
+
+        // the truth terminates after 50 decimal places
+        let s = "sqrt(2/1000003)";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let ((rat, _), _) = actual.eval(s, &Default::default(), 0).unwrap();
+        assert!((BigInt::from(10).pow(50) % rat.denom()).is_zero());
+        let err = (&rat * &rat - "2/1000003".parse::<BigRational>().unwrap())
+            .abs();
+        assert!(err < BigRational::new(1.into(), BigInt::from(10).pow(49)));
+
+        let s = "sqrt(1 - 2)";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let actual = actual.eval(s, &Default::default(), 0);
+        let range = match actual {
+            Err(EvalError::DomainError(range, _)) => range,
+            _ => panic!("expected a domain error: {actual:?}"),
+        };
+        assert_eq!(range, 0..11);
+
+        // estimated under `call` rather than `bin`
+        let actual = parse_line().easy_parse("sqrt(2)").unwrap().0;
+        assert_eq!(actual.expr_ty(), ExprTy::Call);
+        let ctx = EvalContext::new(ExprTy::Call, 1);
+        let opts = EvalOptions::new().with_estimate(vec!["+bin".into()]);
+        assert!(!opts.do_estimate(&ctx));
+        let opts = EvalOptions::new().with_estimate(vec!["+call".into()]);
+        assert!(opts.do_estimate(&ctx));
+    }
+
+    #[test]
+    fn test_radical() {
+        let product = std::f64::consts::SQRT_2 * std::f64::consts::SQRT_2;
+        assert_evaluates_to("sqrt(2) * sqrt(2)", "2", product);
+        assert_evaluates_to("sqrt(8) / sqrt(2)", "2", 2.0);
+        assert_evaluates_to("-sqrt(3) * (sqrt(12))", "-6", -5.999999999999999);
+
+        // the sums are left approximate
+        let s = "sqrt(2) + sqrt(2)";
+        let actual = parse_line().easy_parse(s).unwrap().0;
+        let (res, warnings) = actual.eval_with_warnings(s, &Default::default());
+        let ((rat, _), _) = res.unwrap();
+        assert_ne!(&rat * &rat, BigRational::from_integer(8.into()));
+        let err = (&rat * &rat - BigRational::from_integer(8.into())).abs();
+        assert!(err < BigRational::new(1.into(), BigInt::from(10).pow(48)));
+        assert!(warnings.iter().all(|w| w.kind == WarningKind::IrrationalRoot));
+        assert_eq!(warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_abs_min_max() {
+        assert_evaluates_to("abs(0 - 3/2)", "3/2", 1.5);
+        assert_evaluates_to("min(1/3, 0.3)", "3/10", 0.3);
+        assert_evaluates_to("max(1/3, 0.3, 1 - 2)", "1/3", 1.0 / 3.0);
+        assert_evaluates_to("max( 1 , 2 ) * 2", "4", 4.0);
+
+        // the truths tie, so the first one is taken along with its float
+        assert_evaluates_to("max(0.1 + 0.2, 3/10)", "3/10", 0.1 + 0.2);
+        assert_evaluates_to("max(3/10, 0.1 + 0.2)", "3/10", 0.3);
+        // the floats tie, but not the truths
+        let s = "min(0.30000000000000004, 0.1 + 0.2)";
+        assert_evaluates_to(s, "3/10", 0.1 + 0.2);
+
+        let s = "min(1, 2) + 1";
+        let expr = parse_line().easy_parse(s).unwrap().0;
+        let (_, range) = expr.eval(s, &Default::default(), 0).unwrap();
+        assert_eq!(range, 0..13);
+        match parse_line().easy_parse(s).unwrap().0 {
+            Expr::Add(lhs, _, _) => {
+                let args = match *lhs {
+                    Expr::Call(FnKind::Min, args, _) => args,
+                    actual => panic!("expected `Expr::Call`: {actual:?}"),
+                };
+                assert_eq!(args.len(), 2);
+            }
+            actual => panic!("expected `Expr::Add`: {actual:?}"),
+        }
+
+        for s in ["min(1)", "abs(1, 2)", "sqrt()", "max(1,)"] {
+            assert!(
+                parse_line_checked(s, false, DEFAULT_MAX_DEPTH).is_err(),
+                "{s}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_nested_call() {
+        assert_evaluates_to("abs(min(1, -2))", "2", 2.0);
+
+        let s = "abs(min(1, -2))";
+        let expr = parse_line().easy_parse(s).unwrap().0;
+        let (_, range) = expr.eval(s, &Default::default(), 0).unwrap();
+        assert_eq!(range, 0..15);
+        let args = match parse_line().easy_parse(s).unwrap().0 {
+            Expr::Call(FnKind::Abs, args, range) => {
+                assert_eq!(translate_range(&range, s), 0..15);
+                args
+            }
+            actual => panic!("expected `Expr::Call`: {actual:?}"),
+        };
+        match &args[..] {
+            [Expr::Call(FnKind::Min, args, range)] => {
+                assert_eq!(translate_range(range, s), 4..14);
+                assert_eq!(args.len(), 2);
+            }
+            actual => panic!("expected `Expr::Call`: {actual:?}"),
+        }
+    }
+
+    #[test]
+    fn test_gcd_lcm() {
+        assert_evaluates_to("gcd(462, 1071)", "21", 21.0);
+        assert_evaluates_to("lcm(4, 6)", "12", 12.0);
+        assert_evaluates_to("gcd(0 - 4, 6)", "2", 2.0);
+        assert_evaluates_to("lcm(0, 6)", "0", 0.0);
+        assert_evaluates_to("gcd(10!, 9!) + 1", "362881", 362881.0);
+
+        for s in ["gcd(1/2, 2)", "lcm(4, 0.5)"] {
+            let actual = parse_line().easy_parse(s).unwrap().0;
+            let actual = actual.eval(s, &Default::default(), 0);
+            assert!(matches!(actual, Err(EvalError::DomainError(..))), "{s}");
+        }
+        assert!(parse_line_checked("gcd(1, 2, 3)", false, DEFAULT_MAX_DEPTH)
+            .is_err());
+    }
+
+    /// The symbol of the binary operator at the root, and the operands.
+    fn split_binary(expr: &Expr) -> Option<(char, &Expr, &Expr)> {
+        let (c, lhs, rhs) = match expr {
+            Expr::Add(lhs, rhs, _) => ('+', lhs, rhs),
+            Expr::Sub(lhs, rhs, _) => ('-', lhs, rhs),
+            Expr::Mul(lhs, rhs, _) => ('*', lhs, rhs),
+            Expr::Div(lhs, rhs, _) => ('/', lhs, rhs),
+            Expr::Rem(lhs, rhs, _) => ('%', lhs, rhs),
+            _ => return None,
+        };
+        Some((c, lhs, rhs))
+    }
+
+    #[test]
+    fn test_operator_table() {
+        let binary: Vec<_> =
+            OPERATORS.iter().filter(|op| op.arity == 2).collect();
+        for p in &binary {
+            for q in &binary {
+                let s = format!("8 {} 4 {} 2", p.symbol, q.symbol);
+                let expr = parse_line().easy_parse(s.as_str()).unwrap().0;
+                let (root, lhs, rhs) = split_binary(&expr).unwrap();
+                if p.precedence < q.precedence {
+                    assert_eq!(root, p.symbol, "{s}");
+                    assert_eq!(split_binary(rhs).unwrap().0, q.symbol, "{s}");
+                } else {
+                    // the same precedence groups to the left, as declared
+                    assert_eq!(p.assoc, Assoc::Left);
+                    assert_eq!(root, q.symbol, "{s}");
+                    assert_eq!(split_binary(lhs).unwrap().0, p.symbol, "{s}");
+                }
+            }
+        }
+
+        let prec = |symbol, arity| {
+            let mut it = OPERATORS.iter();
+            it.find(|op| op.symbol == symbol && op.arity == arity).unwrap()
+        };
+        assert_eq!(prec('-', 1).assoc, Assoc::Right);
+        let neg = |s| match parse_line().easy_parse(s).unwrap().0 {
+            Expr::Neg(x, _) => x,
+            actual => panic!("expected `Expr::Neg`: {actual:?}"),
+        };
+        assert!(matches!(*neg("- - 2"), Expr::Neg(..)));
+
+        // `!` binds tighter than the unary `-`, which does than `*`
+        assert!(prec('!', 1).precedence > prec('-', 1).precedence);
+        assert!(prec('-', 1).precedence > prec('*', 2).precedence);
+        assert!(matches!(*neg("- 3!"), Expr::Factorial(..)));
+        let expr = parse_line().easy_parse("- 2 * 3").unwrap().0;
+        assert!(matches!(split_binary(&expr), Some(('*', Expr::Neg(..), _))));
+
+        // the implicit multiplication binds between the two
+        assert!((PREC_NEG..PREC_POSTFIX).contains(&PREC_IMPLICIT));
+        assert!(matches!(*neg("- 2(3)"), Expr::Mul(..)));
+        let rhs = match parse_line().easy_parse("2(3)!").unwrap().0 {
+            Expr::Mul(_, rhs, _) => rhs,
+            actual => panic!("expected `Expr::Mul`: {actual:?}"),
+        };
+        assert!(matches!(*rhs, Expr::Factorial(..)));
+    }
+
+    #[test]
+    fn test_max_depth() {
+        for s in ["1", "(1)", "-(1)", "- 1", "sqrt(1)", "(1) + (2)"] {
+            assert!(parse_line_checked(s, false, 1).is_ok(), "{s}");
+        }
+        for (s, pos) in
+            [("((1))", 2), ("-(-(1))", 2), ("- - 1", 4), ("abs((1))", 5)]
+        {
+            let err = parse_line_checked(s, false, 1).unwrap_err();
+            assert_eq!(err.position, pos, "{s}");
+            let msg = easy::Error::Message("nested too deeply".into());
+            assert!(err.errors.contains(&msg), "{s}");
+        }
+        assert!(parse_statement_checked("x = ((1))", false, 1).is_err());
+
+        // bounded by the limit rather than by the stack
+        let s = "- ".repeat(10000) + "1";
+        assert!(parse_line_checked(&s, false, 16).is_err());
+    }
+
+    #[test]
+    fn test_statement() {
+        let (name, expr) =
+            parse_statement_checked("x = 1/3", true, DEFAULT_MAX_DEPTH)
+                .unwrap();
+        assert_eq!(name.as_deref(), Some("x"));
+        assert!(matches!(expr, Expr::Div(..)));
+        let (name, expr) =
+            parse_statement_checked("ans * 2", true, DEFAULT_MAX_DEPTH)
+                .unwrap();
+        assert_eq!(name, None);
+        assert!(matches!(expr, Expr::Mul(..)));
+        let (_, expr) =
+            parse_statement_checked("x_1 = x_1 + e", true, DEFAULT_MAX_DEPTH)
+                .unwrap();
+        match expr {
+            Expr::Add(lhs, rhs, _) => {
+                assert!(
+                    matches!(*lhs, Expr::Var(ref name, None, _) if name == "x_1")
+                );
+                assert!(matches!(*rhs, Expr::Const(ConstKind::E, _)));
+            }
+            actual => panic!("expected `Expr::Add`: {actual:?}"),
+        }
+
+        let err = parse_statement_checked(" pi = 3", true, DEFAULT_MAX_DEPTH)
+            .unwrap_err();
+        assert_eq!(err.position, 1);
+        for s in ["x =", "= 1", "x = y = 1", "1 = 2", "x == 1"] {
+            assert!(
+                parse_statement_checked(s, false, DEFAULT_MAX_DEPTH).is_err(),
+                "{s}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_program() {
+        let s = "x = 1/3; x * 3;";
+        let mut actual: Vec<_> =
+            parse_program_checked(s, true, DEFAULT_MAX_DEPTH)
+                .into_iter()
+                .collect::<Result<_, _>>()
+                .unwrap();
+        assert_eq!(actual.len(), 2);
+        assert_eq!(actual[0].0.as_deref(), Some("x"));
+        match actual.pop().unwrap().1 {
+            Expr::Mul(lhs, _, range) => {
+                assert_eq!(translate_range(&range, s), 11..12);
+                let actual = lhs.eval(s, &Default::default(), 0);
+                let range = 9..10;
+                assert!(
+                    matches!(actual, Err(EvalError::Undefined(r)) if r == range)
+                );
+            }
+            actual => panic!("expected `Expr::Mul`: {actual:?}"),
+        }
+
+        assert_eq!(
+            parse_program_checked("1 + 2", true, DEFAULT_MAX_DEPTH).len(),
+            1
+        );
+        assert_eq!(
+            parse_program_checked("1; 2 ; ", true, DEFAULT_MAX_DEPTH).len(),
+            2
+        );
+        let test_suite = [
+            ("1; 2 +; 3", vec![None, Some(6), None]),
+            ("1; 2 + 03", vec![None, Some(7)]),
+            ("1+; 2*3", vec![Some(2), None]),
+            ("1 +; * 2; 3", vec![Some(3), Some(5), None]),
+            ("", vec![Some(0)]),
+            (";", vec![Some(0)]),
+            ("1;;2", vec![None, Some(2), None]),
+        ];
+        for (s, expected) in test_suite {
+            let actual: Vec<_> =
+                parse_program_checked(s, true, DEFAULT_MAX_DEPTH)
+                    .into_iter()
+                    .map(|res| res.err().map(|e| e.position))
+                    .collect();
+            assert_eq!(actual, expected, "{s:?}");
+        }
+    }
+
+    #[test]
+    fn test_bad_literal() {
+        let s = "1.2.3";
+        let base = s.as_ptr() as usize;
+        let range =
+            PointerOffset::new(base)..PointerOffset::new(base + s.len());
+        let lit = LitComponent::new(s.to_owned(), 0);
+        assert!(matches!(
+            lit.eval(0..s.len()),
+            Err(EvalError::BadLiteral(r)) if r == (0..5)
+        ));
+        assert!(lit.eval_f32().is_nan());
+        let actual = Expr::Literal(lit, range).eval(s, &Default::default(), 0);
+        assert!(matches!(actual, Err(EvalError::BadLiteral(r)) if r == (0..5)));
+    }
 }
@@ -1,21 +1,51 @@
-use combine::EasyParser;
+use std::{
+    collections::VecDeque,
+    io::{BufRead, IsTerminal, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
 use homedir::get_my_home;
+use num_rational::BigRational;
 use rustyline::{
-    config::{Behavior, Config},
+    completion::{Completer, Pair},
+    config::{Behavior, Config, EditMode},
     error::ReadlineError,
-    DefaultEditor,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::{FileHistory, History},
+    validate::Validator,
+    Context, Editor, Helper,
 };
 
 use crate::{
-    ast::EvalOptions,
-    constants::{AUX_COLOR, PROLOGUE},
-    parser::parse_line,
-    ui::{backmatter, error_report, frontmatter},
-    utils::StrPaint,
+    ast::{
+        check_depth, is_bare_estimate, ErrorUnit, EvalOptions, OutputFormat,
+        Precision, ValueTy, Variables, ESTIMATE_FLAGS, FUNCTIONS,
+    },
+    constants::{MAX_UNDO, PROLOGUE},
+    evaluate,
+    number::{approx_eq, benchmark_decimal},
+    parser::{parse_program_checked, statement_ranges, Statement},
+    ui::{
+        backmatter, error_report, eval_error_msg, frontmatter,
+        json_parse_error, markdown_footer, markdown_header,
+        markdown_parse_error, palette, report_json, take_report_time,
+        timings,
+    },
+    utils::{char_column, with_stack, StrPaint},
+    Error,
 };
 
 pub struct ReplOptions {
-    estimate: Vec<String>,
+    eval: EvalOptions,
+    edit_mode: EditMode,
+    history_size: usize,
+    history_file: Option<PathBuf>,
+    history: bool,
+    scope: Scope,
+    epsilon: Option<BigRational>,
 }
 
 impl Default for ReplOptions {
@@ -23,47 +53,857 @@ impl Default for ReplOptions {
 }
 
 impl ReplOptions {
-    pub fn new() -> Self { Self { estimate: vec![] } }
+    pub fn new() -> Self {
+        Self {
+            eval: EvalOptions::new(),
+            edit_mode: EditMode::Emacs,
+            history_size: 100,
+            history_file: None,
+            history: true,
+            scope: Scope::new(),
+            epsilon: None,
+        }
+    }
 
     pub fn with_estimate(mut self, arg: Vec<String>) -> Self {
-        self.estimate = arg;
+        self.eval.set_estimate(arg);
+        self
+    }
+
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.eval = self.eval.with_format(format);
+        self
+    }
+
+    pub fn with_rounding(mut self, places: Option<usize>) -> Self {
+        self.eval = self.eval.with_rounding(places);
+        self
+    }
+
+    pub fn with_max_terms(mut self, n: Option<usize>) -> Self {
+        self.eval = self.eval.with_max_terms(n);
+        self
+    }
+
+    pub fn with_width(mut self, width: Option<usize>) -> Self {
+        self.eval = self.eval.with_width(width);
+        self
+    }
+
+    pub fn with_strict_literals(mut self, strict: bool) -> Self {
+        self.eval = self.eval.with_strict_literals(strict);
+        self
+    }
+
+    pub fn with_error_unit(mut self, unit: Option<ErrorUnit>) -> Self {
+        self.eval = self.eval.with_error_unit(unit);
+        self
+    }
+
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.eval = self.eval.with_precision(precision);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.eval = self.eval.with_timeout(timeout);
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.eval = self.eval.with_max_depth(max_depth);
+        self
+    }
+
+    pub fn with_estimate_depth(mut self, depth: Option<usize>) -> Self {
+        self.eval = self.eval.with_estimate_depth(depth);
+        self
+    }
+
+    pub fn with_timings(mut self, timings: bool) -> Self {
+        self.eval = self.eval.with_timings(timings);
+        self
+    }
+
+    pub fn with_digit_limit(mut self, limit: Option<usize>) -> Self {
+        self.eval = self.eval.with_digit_limit(limit);
+        self
+    }
+
+    pub fn with_base(mut self, base: Option<u32>) -> Self {
+        self.eval = self.eval.with_base(base);
+        self
+    }
+
+    pub fn with_mixed_fractions(mut self, mixed: bool) -> Self {
+        self.eval = self.eval.with_mixed_fractions(mixed);
+        self
+    }
+
+    pub fn with_group_repeats(mut self, group: bool) -> Self {
+        self.eval = self.eval.with_group_repeats(group);
+        self
+    }
+
+    pub fn with_group_separator(mut self, sep: Option<char>) -> Self {
+        self.eval = self.eval.with_group_separator(sep);
+        self
+    }
+
+    pub fn with_dyadic(mut self, dyadic: bool) -> Self {
+        self.eval = self.eval.with_dyadic(dyadic);
+        self
+    }
+
+    pub fn with_compact_zero(mut self, compact: bool) -> Self {
+        self.eval = self.eval.with_compact_zero(compact);
+        self
+    }
+
+    pub fn with_digits(mut self, places: Option<usize>) -> Self {
+        self.eval = self.eval.with_digits(places);
+        self
+    }
+
+    pub fn with_max_period(mut self, max_period: Option<usize>) -> Self {
+        self.eval = self.eval.with_max_period(max_period);
+        self
+    }
+
+    pub fn with_edit_mode(mut self, edit_mode: EditMode) -> Self {
+        self.edit_mode = edit_mode;
+        self
+    }
+
+    /// Number of entries kept in the history file.
+    pub fn with_history_size(mut self, size: usize) -> Self {
+        self.history_size = size;
+        self
+    }
+
+    /// The history file, instead of the one given by `history_path`.
+    pub fn with_history_file(mut self, path: Option<PathBuf>) -> Self {
+        self.history_file = path;
+        self
+    }
+
+    /// Lets the assertions `x => y` of `--batch` pass when the truths agree
+    /// within the relative tolerance `eps`, as by `approx_eq`, rather than
+    /// only when they are equal.
+    pub fn with_epsilon(mut self, eps: Option<BigRational>) -> Self {
+        self.epsilon = eps;
+        self
+    }
+
+    /// Whether to keep the history, in memory and in the file.
+    pub fn with_history(mut self, enabled: bool) -> Self {
+        self.history = enabled;
         self
     }
+
+    /// The history file in use, if any.
+    pub fn history_file(&self) -> Option<PathBuf> {
+        if !self.history {
+            return None;
+        }
+        self.history_file.clone().or_else(history_path)
+    }
+
+    fn editor_config(&self) -> rustyline::Result<Config> {
+        Ok(Config::builder()
+            .behavior(Behavior::PreferTerm)
+            .edit_mode(self.edit_mode)
+            .max_history_size(self.history_size)?
+            .build())
+    }
 }
 
-pub fn repl(opts: ReplOptions) -> rustyline::Result<()> {
-    let cfg = Config::builder().behavior(Behavior::PreferTerm).build();
-    let mut rl = DefaultEditor::with_config(cfg)?;
+/// The variables, with the assignments that `:undo` reverts.
+#[derive(Clone, Debug, Default)]
+pub struct Scope {
+    vars: Variables,
+    /// The assigned names with their previous values, the latest last, at
+    /// most `MAX_UNDO` of them.
+    undo: VecDeque<(String, Option<ValueTy>)>,
+}
 
-    eprintln!("{PROLOGUE}");
+impl Scope {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn vars(&self) -> &Variables { &self.vars }
+
+    fn assign(&mut self, name: String, val: ValueTy) {
+        let prev = self.vars.insert(name.clone(), val);
+        if self.undo.len() == MAX_UNDO {
+            self.undo.pop_front();
+        }
+        self.undo.push_back((name, prev));
+    }
+
+    /// Reverts the last assignment, restoring the previous value or removing
+    /// the name. The name, and whether it had a value before, unless there
+    /// is nothing to undo.
+    pub fn undo(&mut self) -> Option<(String, bool)> {
+        let (name, prev) = self.undo.pop_back()?;
+        let restored = prev.is_some();
+        match prev {
+            Some(val) => self.vars.insert(name.clone(), val),
+            None => self.vars.remove(&name),
+        };
+        Some((name, restored))
+    }
+}
+
+const BINDINGS_EMACS: &str = r#"
+Ctrl-A, Ctrl-E  Move to the beginning/end of the line
+Alt-B, Alt-F    Move backward/forward by a word
+Ctrl-W          Delete the previous word
+Ctrl-K          Delete to the end of the line
+Ctrl-U          Delete to the beginning of the line
+Ctrl-P, Ctrl-N  Recall the previous/next history entry
+Ctrl-R          Search the history backward
+"#;
+
+const BINDINGS_VI: &str = r#"
+Esc             Switch to the command mode (i, a to insert again)
+0, $            Move to the beginning/end of the line
+b, w            Move backward/forward by a word
+db, dw          Delete the previous/next word
+D               Delete to the end of the line
+k, j            Recall the previous/next history entry
+Ctrl-R          Search the history backward
+"#;
+
+fn bindings(edit_mode: EditMode) -> &'static str {
+    match edit_mode {
+        EditMode::Vi => BINDINGS_VI,
+        _ => BINDINGS_EMACS,
+    }
+}
+
+/// What the loop should do after `handle_command`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Command {
+    /// Not a command; evaluate the line as an expression.
+    Eval,
+    /// The command is handled (or reported as unknown).
+    Skip,
+    Quit,
+}
+
+pub fn handle_command(line: &str, opts: &mut ReplOptions) -> Command {
+    let Some(cmd) = line.trim().strip_prefix(":") else {
+        return Command::Eval;
+    };
+    let (name, arg) = match cmd.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (cmd, ""),
+    };
+    match name {
+        "exit" | "quit" => return Command::Quit,
+        "bindings" => eprintln!("{}", bindings(opts.edit_mode)),
+        "set" if arg.contains("=") => opts.eval.update(arg),
+        "set" => eprintln!(
+            "{}",
+            "usage: :set key=value[; key=value...]".fg(palette().err)
+        ),
+        "show" => opts.eval.show(),
+        "undo" => match opts.scope.undo() {
+            Some((name, true)) => eprintln!("restored the previous {name}"),
+            Some((name, false)) => eprintln!("removed {name}"),
+            None => eprintln!("nothing to undo"),
+        },
+        "help" | "version" => opts.eval.update(name),
+        // `:key=value` as `:set key=value`, and `:estimate` showing it
+        _ if name.starts_with("estimate") || cmd.contains("=") => {
+            opts.eval.update(cmd)
+        }
+        _ => {
+            let msg = format!("unknown command: :{name} (see :help)");
+            eprintln!("{}", msg.fg(palette().err));
+        }
+    }
+    Command::Skip
+}
+
+/// The names completed after `:`.
+const COMMANDS: [&str; 9] = [
+    "help",
+    "version",
+    "bindings",
+    "show",
+    "set",
+    "estimate=",
+    "undo",
+    "quit",
+    "exit",
+];
+
+/// The keys completed after `:set`.
+const KEYS: [&str; 2] = ["estimate=", "format="];
+
+/// Completes the commands, the keys of `:set`, and their values, with the
+/// current values as hints, and the names of the functions and the variables
+/// in the expressions.
+struct CommandCompleter {
+    opts: EvalOptions,
+    vars: Variables,
+}
+
+impl Completer for CommandCompleter {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(complete_command(&line[..pos], &self.opts, &self.vars))
+    }
+}
+
+impl Hinter for CommandCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for CommandCompleter {}
+
+impl Validator for CommandCompleter {}
+
+impl Helper for CommandCompleter {}
+
+/// The start of the word before the cursor at the end of `line`, and the
+/// candidates replacing it.
+fn complete_command(
+    line: &str,
+    opts: &EvalOptions,
+    vars: &Variables,
+) -> (usize, Vec<Pair>) {
+    let none = (line.len(), vec![]);
+    let Some(cmd) = line.trim_start().strip_prefix(":") else {
+        return complete_name(line, vars);
+    };
+    let start = line.len() - cmd.len();
+    let (start, word) = match cmd.split_once(char::is_whitespace) {
+        Some(("set", _)) => {
+            let i = line.rfind(|c: char| c == ';' || c.is_whitespace());
+            let i = i.map_or(0, |i| i + 1);
+            (i, &line[i..])
+        }
+        // `:estimate=...`
+        _ if cmd.contains("=") => (start, cmd),
+        Some(_) => return none,
+        None => {
+            return (start, candidates(cmd, COMMANDS.map(str::to_owned)));
+        }
+    };
+    let Some((key, value)) = word.split_once("=") else {
+        return (start, candidates(word, KEYS.map(str::to_owned)));
+    };
+    let start = start + key.len() + 1;
+    let (i, current, values) = match key {
+        "estimate" => {
+            let i = value.rfind(",").map_or(0, |i| i + 1);
+            let bare = ESTIMATE_FLAGS
+                .iter()
+                .filter(|&&(_, bit, _)| is_bare_estimate(bit))
+                .map(|&(name, ..)| name.to_owned());
+            let signed = ESTIMATE_FLAGS
+                .iter()
+                .flat_map(|(s, ..)| [format!("+{s}"), format!("-{s}")]);
+            (i, opts.estimate(), bare.chain(signed).collect())
+        }
+        "format" => {
+            let values = ["pretty", "markdown", "json"].map(str::to_owned);
+            (0, opts.format().to_string(), values.to_vec())
+        }
+        _ => return none,
+    };
+    let token = value[i..].trim_start();
+    let i = value.len() - token.len();
+    let mut res = candidates(token, values);
+    if value.is_empty() {
+        let display = format!("{current} (current)");
+        res.insert(0, Pair { display, replacement: current });
+    }
+    (start + i, res)
+}
+
+/// Completes the name before the cursor in an expression, either of a
+/// function or of a variable in `vars`.
+fn complete_name(line: &str, vars: &Variables) -> (usize, Vec<Pair>) {
+    let start = line
+        .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        .len();
+    let word = &line[start..];
+    if !word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return (line.len(), vec![]);
+    }
+    let fns = FUNCTIONS.map(|info| info.name.to_owned());
+    let mut names: Vec<_> = vars.keys().cloned().collect();
+    names.sort();
+    (start, candidates(word, fns.into_iter().chain(names)))
+}
+
+fn candidates<I: IntoIterator<Item = String>>(
+    prefix: &str,
+    all: I,
+) -> Vec<Pair> {
+    all.into_iter()
+        .filter(|s| s.starts_with(prefix))
+        .map(|s| Pair { display: s.clone(), replacement: s })
+        .collect()
+}
+
+/// The default history file, `$XDG_STATE_HOME/feather-repl/history`, or
+/// `~/.float_repl_history` if `XDG_STATE_HOME` is not set. `None` if
+/// neither directory is known.
+pub fn history_path() -> Option<PathBuf> {
+    let state_home = std::env::var_os("XDG_STATE_HOME").map(PathBuf::from);
+    let home = get_my_home().ok().flatten();
+    history_path_in(state_home, home)
+}
+
+fn history_path_in(
+    state_home: Option<PathBuf>,
+    home: Option<PathBuf>,
+) -> Option<PathBuf> {
+    // relative ones are invalid by the spec
+    match state_home.filter(|dir| dir.is_absolute()) {
+        Some(dir) => Some(dir.join("feather-repl").join("history")),
+        None => Some(home?.join(".float_repl_history")),
+    }
+}
+
+/// Prints the entries of the history file, oldest first.
+///
+/// A missing file is treated as an empty history.
+pub fn dump_history(
+    path: &Path,
+    out: &mut impl Write,
+) -> rustyline::Result<()> {
+    let mut history = FileHistory::new();
+    if path.exists() {
+        history.load(path)?;
+    }
+    for entry in history.iter() {
+        writeln!(out, "{entry}")?;
+    }
+    Ok(())
+}
+
+/// Prints the functions, each with the number of arguments it takes.
+pub fn list_functions(out: &mut impl Write) -> std::io::Result<()> {
+    for info in &FUNCTIONS {
+        writeln!(out, "{:<8}{:<4}{}", info.name, info.arity, info.desc)?;
+    }
+    Ok(())
+}
+
+/// Saves the history, keeping only the most recent `size` entries.
+fn save_history(
+    history: &mut FileHistory,
+    path: &Path,
+    size: usize,
+) -> rustyline::Result<()> {
+    history.set_max_len(size)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    history.save(path)
+}
+
+/// Parses, evaluates, and reports the `nl`-th line, each of the
+/// `;`-separated statements in its own block. The values are stored to
+/// `scope`, under the assigned names or as `ans`.
+pub fn eval_line(
+    line: &str,
+    opts: &EvalOptions,
+    scope: &mut Scope,
+    nl: usize,
+) -> Result<(), Error> {
+    with_stack(|| eval_line_(line, opts, scope, nl))
+}
+
+fn eval_line_(
+    line: &str,
+    opts: &EvalOptions,
+    scope: &mut Scope,
+    nl: usize,
+) -> Result<(), Error> {
+    if let Err(e) = check_depth(line, opts) {
+        match opts.format() {
+            OutputFormat::Pretty => {
+                frontmatter("stdin", nl);
+                backmatter(line, Err(e.clone()), &[]);
+            }
+            OutputFormat::Markdown => {
+                print!("{}", markdown_header(line));
+                print!("{}", markdown_footer(line, &Err(e.clone()), &[]));
+            }
+            OutputFormat::Json => {
+                let res = Err(e.clone());
+                let report = report_json(line, 0..line.len(), &res, &[], opts);
+                println!("{report}");
+            }
+        }
+        return Err(Error::Eval(e));
+    }
+    let mut res = Ok(());
+    let start = Instant::now();
+    let stmts =
+        parse_program_checked(line, opts.strict_literals(), opts.max_depth());
+    let parsed = start.elapsed();
+    for (stmt, range) in stmts.into_iter().zip(statement_ranges(line)) {
+        // the ones after an error are still evaluated
+        res = res.and(match stmt {
+            Ok(stmt) => {
+                eval_statement(line, range, stmt, opts, scope, nl, parsed)
+            }
+            Err(e) => {
+                match opts.format() {
+                    OutputFormat::Pretty => {
+                        frontmatter("stdin", nl);
+                        error_report(&e, line);
+                    }
+                    OutputFormat::Markdown => {
+                        print!("{}", markdown_parse_error(line, &e))
+                    }
+                    OutputFormat::Json => {
+                        println!("{}", json_parse_error(line, range, &e))
+                    }
+                }
+                Err(Error::Parse(e))
+            }
+        });
+    }
+    res
+}
+
+/// Evaluates and reports the statement `line[range]`, which took `parsed`
+/// to parse.
+fn eval_statement(
+    line: &str,
+    range: Range<usize>,
+    (name, mut ast): Statement,
+    opts: &EvalOptions,
+    scope: &mut Scope,
+    nl: usize,
+    parsed: Duration,
+) -> Result<(), Error> {
+    match opts.format() {
+        OutputFormat::Pretty => frontmatter("stdin", nl),
+        OutputFormat::Markdown => print!("{}", markdown_header(line)),
+        OutputFormat::Json => {}
+    }
+    ast.bind(&scope.vars);
+    take_report_time();
+    let start = Instant::now();
+    let (res, warnings) = ast.eval_with_warnings(line, opts);
+    let evaluated = start.elapsed().saturating_sub(take_report_time());
+    if let Ok((val, _)) = &res {
+        match name {
+            Some(name) => scope.assign(name, val.clone()),
+            None => {
+                scope.vars.insert("ans".to_owned(), val.clone());
+            }
+        }
+    }
+    let err = res.as_ref().err().cloned().map(Error::Eval);
+    match opts.format() {
+        OutputFormat::Pretty => backmatter(line, res, &warnings),
+        OutputFormat::Markdown => {
+            print!("{}", markdown_footer(line, &res, &warnings))
+        }
+        OutputFormat::Json => {
+            let report = report_json(line, range, &res, &warnings, opts);
+            println!("{report}")
+        }
+    }
+    if opts.timings() {
+        timings(parsed, evaluated);
+    }
+    err.map_or(Ok(()), Err)
+}
+
+/// Strips what the files written on other platforms may have, the trailing
+/// `\r`, and the BOM if `line` is the first line of the input.
+fn normalize_line(line: &str, first: bool) -> &str {
+    let line = if first {
+        line.strip_prefix('\u{feff}').unwrap_or(line)
+    } else {
+        line
+    };
+    line.trim_end_matches('\r')
+}
+
+/// Evaluates a single line without entering the loop, for `--eval`.
+pub fn eval_once(line: &str, opts: &ReplOptions) -> Result<(), Error> {
+    let mut scope = opts.scope.clone();
+    eval_line(normalize_line(line, true), &opts.eval, &mut scope, 1)
+}
+
+/// Times the decimal expansion of the value of `expr` for
+/// `--benchmark-decimal`, up to the period of `EvalOptions::max_period`.
+/// Returns whether `expr` evaluated, reporting the error as `eval_once`
+/// otherwise.
+pub fn benchmark_once(
+    expr: &str,
+    opts: &ReplOptions,
+    out: &mut impl Write,
+) -> std::io::Result<bool> {
+    let Ok((rat, _)) = evaluate(expr, &opts.eval) else {
+        let _ = eval_once(expr, opts);
+        return Ok(false);
+    };
+    let max_period = opts.eval.max_period();
+    let (period, elapsed) = benchmark_decimal(&rat, max_period);
+    match period {
+        Some((once, rep)) => {
+            writeln!(out, "pre-period: {once} digits")?;
+            writeln!(out, "    period: {rep} digits")?;
+        }
+        None => writeln!(out, "    period: > {max_period} digits")?,
+    }
+    writeln!(out, "   elapsed: {elapsed:?}")?;
+    Ok(true)
+}
+
+/// Counts of the lines of a batch by the outcome.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BatchSummary {
+    pub ok: usize,
+    /// The assertions `x => y` whose truths differ.
+    pub failed: usize,
+    pub parse_errors: usize,
+    pub eval_errors: usize,
+}
+
+impl BatchSummary {
+    pub fn is_success(&self) -> bool {
+        self.failed == 0 && self.parse_errors == 0 && self.eval_errors == 0
+    }
+}
+
+impl std::fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ok, {} failed, {} parse errors, {} eval errors",
+            self.ok, self.failed, self.parse_errors, self.eval_errors
+        )
+    }
+}
 
-    let histfile = get_my_home().unwrap().unwrap().join(".float_repl_history");
+/// Evaluates each line of the file at `path` for `--batch`, printing a
+/// line of the result for each and the summary to stdout.
+pub fn run_batch(
+    path: &Path,
+    opts: &ReplOptions,
+) -> std::io::Result<BatchSummary> {
+    let input = std::io::BufReader::new(std::fs::File::open(path)?);
+    let mut out = std::io::stdout().lock();
+    let summary = run_batch_lines(input, opts, &mut out)?;
+    writeln!(out, "{summary}")?;
+    Ok(summary)
+}
 
-    if rl.load_history(&histfile).is_err() {
-        eprintln!("No previous history.");
+/// Evaluates each line by `evaluate`, skipping the blank ones and the
+/// comments starting with `#`. A line `x => y` asserts that the truths of
+/// `x` and `y` are equal, e.g. `1/3 + 1/6 => 1/2`. Lines may end with
+/// `\n`, `\r\n`, or `\r`, as in `run_lines`.
+fn run_batch_lines(
+    input: impl BufRead,
+    opts: &ReplOptions,
+    out: &mut impl Write,
+) -> std::io::Result<BatchSummary> {
+    let mut summary = BatchSummary::default();
+    let mut nl = 0;
+    for line in input.lines() {
+        let line = line?;
+        for line in normalize_line(&line, nl == 0).split('\r') {
+            nl += 1;
+            run_batch_line(line.trim(), nl, opts, &mut summary, out)?;
+        }
     }
+    Ok(summary)
+}
 
-    let mut opts = EvalOptions::default().with_estimate(opts.estimate);
+/// Evaluates the `nl`-th line of a batch, counting it in `summary`.
+fn run_batch_line(
+    line: &str,
+    nl: usize,
+    opts: &ReplOptions,
+    summary: &mut BatchSummary,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    if line.is_empty() || line.starts_with("#") {
+        return Ok(());
+    }
+    let (expr, expected) = match line.split_once("=>") {
+        Some((expr, expected)) => (expr.trim(), Some(expected.trim())),
+        None => (line, None),
+    };
+    let res = evaluate(expr, &opts.eval).map_err(|e| (expr, e));
+    let expected = expected.map(|y| {
+        evaluate(y, &opts.eval).map(|(rat, _)| (y, rat)).map_err(|e| (y, e))
+    });
+    let agree = |x: &BigRational, y: &BigRational| match &opts.epsilon {
+        Some(eps) => approx_eq(x, y, eps),
+        None => x == y,
+    };
+    match (res, expected.transpose()) {
+        (Ok((rat, flt)), Ok(None)) => {
+            summary.ok += 1;
+            writeln!(out, "{nl}: {line} = {rat} (float: {flt:?})")?;
+        }
+        (Ok((rat, _)), Ok(Some((y, exp)))) if agree(&rat, &exp) => {
+            summary.ok += 1;
+            writeln!(out, "{nl}: PASS {expr} => {y}")?;
+        }
+        (Ok((rat, _)), Ok(Some((_, expected)))) => {
+            summary.failed += 1;
+            writeln!(out, "{nl}: FAIL {expr} = {rat}, not {expected}")?;
+        }
+        // the error of the left side before the one of the right side
+        (Err((s, e)), _) | (Ok(_), Err((s, e))) => match e {
+            Error::Parse(e) => {
+                summary.parse_errors += 1;
+                let col = char_column(s, e.position);
+                writeln!(out, "{nl}: {s}: parse error at column {col}")?;
+            }
+            Error::Eval(e) => {
+                summary.eval_errors += 1;
+                let (range, msg) = eval_error_msg(&e);
+                writeln!(out, "{nl}: {s}: {msg} at `{}`", &s[range])?;
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Evaluates each line of a non-interactive input, e.g. a pipe. Lines may
+/// end with `\n`, `\r\n`, or `\r`.
+fn run_lines(
+    input: impl BufRead,
+    opts: &mut ReplOptions,
+) -> rustyline::Result<()> {
+    let mut nl = 0;
+    'input: for line in input.lines() {
+        let line = line?;
+        for line in normalize_line(&line, nl == 0).split('\r') {
+            nl += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match handle_command(line, opts) {
+                Command::Quit => break 'input,
+                Command::Skip => {}
+                Command::Eval => {
+                    let _ = eval_line(line, &opts.eval, &mut opts.scope, nl);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether the input goes on to the next line, for ending with `\` or
+/// leaving a parenthesis open, e.g. `(1 +`. Commands never do.
+fn continues(src: &str) -> bool {
+    if src.trim_start().starts_with(":") {
+        return false;
+    }
+    if src.trim_end().ends_with('\\') {
+        return true;
+    }
+    let mut depth = 0_usize;
+    for c in src.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+/// Appends the next `line` to `src`, dropping the `\` that continued it,
+/// or separating them by a space otherwise. The result is parsed as one
+/// line, so the errors point into it.
+fn join_line(src: &mut String, line: &str) {
+    if src.is_empty() {
+        src.push_str(line);
+        return;
+    }
+    match src.trim_end().strip_suffix('\\') {
+        Some(head) => src.truncate(head.len()),
+        None => src.push(' '),
+    }
+    src.push_str(line);
+}
+
+pub fn repl(mut opts: ReplOptions) -> rustyline::Result<()> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return run_lines(stdin.lock(), &mut opts);
+    }
+
+    let mut rl: Editor<_, FileHistory> =
+        Editor::with_config(opts.editor_config()?)?;
+    let vars = opts.scope.vars().clone();
+    rl.set_helper(Some(CommandCompleter { opts: opts.eval, vars }));
+
+    eprintln!("{PROLOGUE}");
+
+    let histfile = opts.history_file();
+    match &histfile {
+        Some(path) if rl.load_history(path).is_err() => {
+            eprintln!("No previous history.");
+        }
+        Some(_) => {}
+        None if opts.history => {
+            eprintln!("No home directory; the history is not saved.");
+        }
+        None => {}
+    }
+
+    // the lines read so far of an input continued to the next line
+    let mut src = String::new();
     for nl in 1.. {
-        let readline = rl.readline(&">> ".fg(AUX_COLOR).to_string());
+        let prompt = if src.is_empty() { ">> " } else { ".. " };
+        let readline = rl.readline(&prompt.fg(palette().aux).to_string());
         match readline {
-            Ok(line) if line.trim().is_empty() => {}
-            Ok(line) if [":exit", ":quit"].contains(&line.trim()) => break,
-            Ok(line) if line.starts_with(":") => {
-                rl.add_history_entry(line.to_owned())?;
-                opts.update(&line[1..]);
-            }
+            Ok(line) if src.is_empty() && line.trim().is_empty() => {}
             Ok(line) => {
-                rl.add_history_entry(line.to_owned())?;
-                frontmatter("stdin", nl);
-                match parse_line().easy_parse(line.as_str()) {
-                    Ok(ast) => backmatter(&line, ast.0.eval(&line, &opts, 0)),
-                    Err(e) => error_report(e, &line),
+                join_line(&mut src, &line);
+                if continues(&src) {
+                    continue;
+                }
+                let line = std::mem::take(&mut src);
+                let cmd = handle_command(&line, &mut opts);
+                if cmd != Command::Quit && opts.history {
+                    rl.add_history_entry(line.to_owned())?;
+                }
+                match cmd {
+                    Command::Quit => break,
+                    Command::Skip => {}
+                    Command::Eval => {
+                        // errors are already reported by `eval_line`
+                        let _ =
+                            eval_line(&line, &opts.eval, &mut opts.scope, nl);
+                    }
+                }
+                if let Some(helper) = rl.helper_mut() {
+                    helper.opts = opts.eval;
+                    helper.vars = opts.scope.vars().clone();
                 }
             }
 
             Err(ReadlineError::Interrupted) => {
                 eprintln!("^C");
+                src.clear();
                 continue;
             }
             Err(ReadlineError::Eof) => {
@@ -77,7 +917,490 @@ pub fn repl(opts: ReplOptions) -> rustyline::Result<()> {
         }
     }
 
-    rl.save_history(&histfile)?;
+    if let Some(path) = &histfile {
+        save_history(rl.history_mut(), path, opts.history_size)?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::EvalError, constants::DEFAULT_MAX_DEPTH};
+    use num::BigRational;
+
+    /// A path in the temporary directory of its own to the test `name` and
+    /// to this process, so that the parallel tests and runs do not collide.
+    fn temp_path(name: &str) -> PathBuf {
+        let file = format!("feather_repl_{name}_{}", std::process::id());
+        std::env::temp_dir().join(file)
+    }
+
+    #[test]
+    fn test_dump_history() {
+        let path = temp_path("test_dump_history");
+        std::fs::write(&path, "#V2\n1 + 2\n3 * 4\n1 / 3\n").unwrap();
+        let mut out = vec![];
+        dump_history(&path, &mut out).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1 + 2\n3 * 4\n1 / 3\n");
+
+        let mut out = vec![];
+        dump_history(&path, &mut out).unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_history_path() {
+        let state = Some(PathBuf::from("/state"));
+        let home = Some(PathBuf::from("/home/user"));
+        let actual = history_path_in(state.clone(), home.clone());
+        assert_eq!(actual, Some(PathBuf::from("/state/feather-repl/history")));
+        let actual = history_path_in(state, None);
+        assert_eq!(actual, Some(PathBuf::from("/state/feather-repl/history")));
+        let expected = Some(PathBuf::from("/home/user/.float_repl_history"));
+        assert_eq!(history_path_in(None, home.clone()), expected);
+        let relative = Some(PathBuf::from("state"));
+        assert_eq!(history_path_in(relative, home), expected);
+        assert_eq!(history_path_in(None, None), None);
+
+        let path = PathBuf::from("/tmp/history");
+        let opts = ReplOptions::new().with_history_file(Some(path.clone()));
+        assert_eq!(opts.history_file(), Some(path));
+        assert_eq!(opts.with_history(false).history_file(), None);
+    }
+
+    #[test]
+    fn test_save_history_creates_dirs() {
+        let dir = temp_path("test_save_history_creates_dirs");
+        let path = dir.join("nested").join("history");
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut history = FileHistory::new();
+        history.add("1 + 2").unwrap();
+        save_history(&mut history, &path, 100).unwrap();
+        let mut out = vec![];
+        dump_history(&path, &mut out).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1 + 2\n");
+    }
+
+    #[test]
+    fn test_complete_command() {
+        let opts = EvalOptions::new();
+        let vars = Variables::new();
+        let complete = |line: &str| {
+            let (start, res) = complete_command(line, &opts, &vars);
+            let res: Vec<_> = res.into_iter().map(|p| p.replacement).collect();
+            (start, res)
+        };
+        assert_eq!(complete(":s"), (1, vec!["show".into(), "set".into()]));
+        assert_eq!(complete(" :q"), (2, vec!["quit".into()]));
+        assert_eq!(complete(":set f"), (5, vec!["format=".into()]));
+        assert_eq!(complete(":set estimate=+l"), (14, vec!["+lit".into()]));
+        assert_eq!(
+            complete(":estimate=lit, -c"),
+            (15, vec!["-call".into(), "-cf".into(), "-cancel".into()])
+        );
+        assert_eq!(
+            complete(":set estimate=+bin; format=m"),
+            (27, vec!["markdown".into()])
+        );
+
+        let (_, res) = complete_command(":set format=", &opts, &vars);
+        assert_eq!(res[0].display, "pretty (current)");
+        let (_, res) = complete_command(":estimate=", &opts, &vars);
+        assert_eq!(res[0].replacement, opts.estimate());
+        assert!(res.iter().any(|p| p.replacement == "each"));
+
+        for line in ["1 + 2", "", "sqrt(", ":show x"] {
+            assert_eq!(complete(line), (line.len(), vec![]), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_complete_name() {
+        let opts = EvalOptions::new();
+        let mut scope = Scope::new();
+        let vars = Variables::new();
+        let (start, res) = complete_command("1 + sq", &opts, &vars);
+        assert_eq!(start, 4);
+        let res: Vec<_> = res.into_iter().map(|p| p.replacement).collect();
+        assert_eq!(res, ["sqrt"]);
+
+        let (_, res) = complete_command("1 + x", &opts, &vars);
+        assert!(res.is_empty());
+        eval_line("x = 1/3", &opts, &mut scope, 1).unwrap();
+        let (start, res) = complete_command("2 * (x", &opts, scope.vars());
+        assert_eq!(start, 5);
+        let res: Vec<_> = res.into_iter().map(|p| p.replacement).collect();
+        assert_eq!(res, ["x"]);
+    }
+
+    #[test]
+    fn test_continued_lines() {
+        let test_suite: [(&[_], _); 6] = [
+            (&["1 + 2"], "1 + 2"),
+            (&["(1 +", "2)"], "(1 + 2)"),
+            (&["1 + \\", "2"], "1 + 2"),
+            (&["12\\", "3"], "123"),
+            (&["sqrt((1", "+ 2)", ") * 3"], "sqrt((1 + 2) ) * 3"),
+            (&["(1 + \\ ", "2", ")"], "(1 + 2 )"),
+        ];
+        for (lines, expected) in test_suite {
+            let mut src = String::new();
+            for (i, line) in lines.iter().enumerate() {
+                join_line(&mut src, line);
+                assert_eq!(continues(&src), i + 1 < lines.len(), "{src}");
+            }
+            assert_eq!(src, expected);
+        }
+        assert!(!continues(":set estimate=+bin \\"));
+        assert!(!continues("1) + (2))"));
+    }
+
+    #[test]
+    fn test_run_batch() {
+        let input = "# exact\n1/3 + 1/6\n\n0.1 + 0.2\n1 +\n  1/(1 - 1)\n";
+        let mut out = vec![];
+        let opts = ReplOptions::new();
+        let summary = run_batch_lines(input.as_bytes(), &opts, &mut out);
+        let expected =
+            BatchSummary { ok: 2, failed: 0, parse_errors: 1, eval_errors: 1 };
+        assert_eq!(summary.unwrap(), expected);
+        assert!(!expected.is_success());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2: 1/3 + 1/6 = 1/2 (float: 0.5)\n\
+             4: 0.1 + 0.2 = 3/10 (float: 0.30000000000000004)\n\
+             5: 1 +: parse error at column 4\n\
+             6: 1/(1 - 1): divide by zero at `1/(1 - 1)`\n"
+        );
+        assert_eq!(
+            expected.to_string(),
+            "2 ok, 0 failed, 1 parse errors, 1 eval errors"
+        );
+    }
+
+    #[test]
+    fn test_run_batch_assertions() {
+        let input = "1/3 + 1/6 => 1/2\n0.1 + 0.2 => 0.3\n0.1 * 3 => 1/3\n\
+                     1 => (2\n1 => 1/0\n1/0 => (2\n";
+        let mut out = vec![];
+        let opts = ReplOptions::new();
+        let summary = run_batch_lines(input.as_bytes(), &opts, &mut out);
+        let expected =
+            BatchSummary { ok: 2, failed: 1, parse_errors: 1, eval_errors: 2 };
+        assert_eq!(summary.unwrap(), expected);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1: PASS 1/3 + 1/6 => 1/2\n\
+             2: PASS 0.1 + 0.2 => 0.3\n\
+             3: FAIL 0.1 * 3 = 3/10, not 1/3\n\
+             4: (2: parse error at column 3\n\
+             5: 1/0: divide by zero at `1/0`\n\
+             6: 1/0: divide by zero at `1/0`\n"
+        );
+
+        // exact unless --epsilon is given
+        let input = "0.1 + 0.2 => 0.30001\n0.1 + 0.2 => 0.3001\n";
+        let run = |opts: &ReplOptions| {
+            let mut out = vec![];
+            run_batch_lines(input.as_bytes(), opts, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(
+            run(&ReplOptions::new()),
+            "1: FAIL 0.1 + 0.2 = 3/10, not 30001/100000\n\
+             2: FAIL 0.1 + 0.2 = 3/10, not 3001/10000\n"
+        );
+        let eps = "1/10000".parse().unwrap();
+        assert_eq!(
+            run(&ReplOptions::new().with_epsilon(Some(eps))),
+            "1: PASS 0.1 + 0.2 => 0.30001\n\
+             2: FAIL 0.1 + 0.2 = 3/10, not 3001/10000\n"
+        );
+    }
+
+    #[test]
+    fn test_benchmark_once() {
+        let run = |expr: &str, opts: &ReplOptions| {
+            let mut out = vec![];
+            let ok = benchmark_once(expr, opts, &mut out).unwrap();
+            (ok, String::from_utf8(out).unwrap())
+        };
+        let (ok, out) = run("1/7", &ReplOptions::new());
+        assert!(ok);
+        assert!(out.starts_with("pre-period: 0 digits\n    period: 6 digits\n"));
+
+        let opts = ReplOptions::new().with_max_period(Some(1000));
+        let (ok, out) = run("1/982451653", &opts);
+        assert!(ok);
+        assert!(out.starts_with("    period: > 1000 digits\n"));
+
+        let opts = ReplOptions::new().with_strict_literals(true);
+        assert_eq!(run("007", &opts), (false, String::new()));
+    }
+
+    #[test]
+    fn test_list_functions() {
+        let mut out = vec![];
+        list_functions(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), FUNCTIONS.len());
+        assert_eq!(
+            lines[0],
+            "sqrt    1   Square root, exact for the perfect squares"
+        );
+        assert_eq!(lines[2], "min     2+  Minimum of the truths");
+        assert_eq!(lines[4], "gcd     2   GCD of integers");
+    }
+
+    #[test]
+    fn test_edit_mode() {
+        let opts = ReplOptions::new();
+        let cfg = opts.editor_config().unwrap();
+        assert_eq!(cfg.edit_mode(), EditMode::Emacs);
+        let opts = opts.with_edit_mode(EditMode::Vi);
+        let cfg = opts.editor_config().unwrap();
+        assert_eq!(cfg.edit_mode(), EditMode::Vi);
+    }
+
+    #[test]
+    fn test_save_history() {
+        let path = temp_path("test_save_history");
+        let mut history = FileHistory::new();
+        for i in 0..5 {
+            history.add(&format!("{i} + {i}")).unwrap();
+        }
+        save_history(&mut history, &path, 3).unwrap();
+        let mut out = vec![];
+        dump_history(&path, &mut out).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "2 + 2\n3 + 3\n4 + 4\n");
+    }
+
+    #[test]
+    fn test_handle_command() {
+        let mut opts = ReplOptions::new();
+        assert_eq!(handle_command("1 + 2", &mut opts), Command::Eval);
+        assert_eq!(handle_command(" :quit ", &mut opts), Command::Quit);
+        assert_eq!(handle_command(":exit", &mut opts), Command::Quit);
+        assert_eq!(handle_command(":show", &mut opts), Command::Skip);
+        assert_eq!(handle_command(":nonsense", &mut opts), Command::Skip);
+
+        let cmd = handle_command(":set estimate=+bin,+par", &mut opts);
+        assert_eq!(cmd, Command::Skip);
+        let expected = vec!["+bin,+par".to_owned()];
+        assert_eq!(opts.eval, EvalOptions::new().with_estimate(expected));
+
+        handle_command(":estimate=-par", &mut opts);
+        let expected = vec!["+bin".to_owned()];
+        assert_eq!(opts.eval, EvalOptions::new().with_estimate(expected));
+
+        // a bare kind replaces the kinds, keeping the other flags
+        handle_command(":estimate=+cf,+each; estimate=lit", &mut opts);
+        let expected = vec!["+lit,+cf".to_owned()];
+        assert_eq!(opts.eval, EvalOptions::new().with_estimate(expected));
+        // but a bare flag is not a kind and is ignored
+        let before = opts.eval;
+        handle_command(":estimate=cf", &mut opts);
+        assert_eq!(opts.eval, before);
+
+        // the current value reads back as itself
+        let current = opts.eval.estimate();
+        let expected = EvalOptions::new().with_estimate(vec![current.clone()]);
+        assert_eq!(expected.estimate(), current);
+    }
+
+    #[test]
+    fn test_eval_once() {
+        let opts = ReplOptions::new();
+        assert!(eval_once("1/3 + 1/6", &opts).is_ok());
+        assert!(matches!(eval_once("1/0", &opts), Err(Error::Eval(_))));
+        assert!(matches!(eval_once("1 +", &opts), Err(Error::Parse(_))));
+    }
+
+    #[test]
+    fn test_run_lines() {
+        let input = "1/3\n\n:set estimate=+bin\n:quit\n:set estimate=+lit\n";
+        let mut opts = ReplOptions::new();
+        run_lines(input.as_bytes(), &mut opts).unwrap();
+        let expected = vec!["+bin".to_owned()];
+        assert_eq!(opts.eval, EvalOptions::new().with_estimate(expected));
+
+        let mut opts = ReplOptions::new();
+        run_lines(":format=json\n".as_bytes(), &mut opts).unwrap();
+        assert_eq!(opts.eval.format(), OutputFormat::Json);
+        run_lines(":format = markdown\n".as_bytes(), &mut opts).unwrap();
+        assert_eq!(opts.eval.format(), OutputFormat::Markdown);
+    }
+
+    #[test]
+    fn test_variables() {
+        let input = "x = 1/3\nx * 3\ny = ans + x\n";
+        let mut opts = ReplOptions::new();
+        run_lines(input.as_bytes(), &mut opts).unwrap();
+        let third = BigRational::new(1.into(), 3.into());
+        let vars = opts.scope.vars();
+        assert_eq!(vars["x"].0, third);
+        assert_eq!(vars["ans"].0, BigRational::from_integer(1.into()));
+        assert_eq!(vars["y"].0, BigRational::new(4.into(), 3.into()));
+
+        let mut scope = Scope::new();
+        let opts = EvalOptions::new();
+        let res = eval_line("x + 1", &opts, &mut scope, 1);
+        assert!(matches!(res, Err(Error::Eval(EvalError::Undefined(_)))));
+        let res = eval_line("pi = 3", &opts, &mut scope, 1);
+        assert!(matches!(res, Err(Error::Parse(_))));
+        assert!(scope.vars().is_empty());
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut opts = ReplOptions::new();
+        run_lines("x=1\nx=2\n:undo\n".as_bytes(), &mut opts).unwrap();
+        let one = BigRational::from_integer(1.into());
+        assert_eq!(opts.scope.vars()["x"].0, one);
+        assert_eq!(handle_command(":undo", &mut opts), Command::Skip);
+        assert!(!opts.scope.vars().contains_key("x"));
+        assert_eq!(opts.scope.undo(), None);
+
+        let mut scope = Scope::new();
+        let opts = EvalOptions::new();
+        eval_line("y = 1; y + 1", &opts, &mut scope, 1).unwrap();
+        assert_eq!(scope.undo(), Some(("y".to_owned(), false)));
+        assert!(scope.vars().contains_key("ans"));
+    }
+
+    #[test]
+    fn test_undo_limit() {
+        let mut scope = Scope::new();
+        let opts = EvalOptions::new();
+        for i in 0..MAX_UNDO + 2 {
+            eval_line(&format!("x = {i}"), &opts, &mut scope, 1).unwrap();
+        }
+        for _ in 0..MAX_UNDO {
+            assert_eq!(scope.undo(), Some(("x".to_owned(), true)));
+        }
+        assert_eq!(scope.undo(), None);
+        // The two oldest assignments are forgotten, not reverted.
+        let one = BigRational::from_integer(1.into());
+        assert_eq!(scope.vars()["x"].0, one);
+    }
+
+    #[test]
+    fn test_statements() {
+        let mut scope = Scope::new();
+        let opts = EvalOptions::new();
+        let res = eval_line("x = 1/3; y = x * 3;", &opts, &mut scope, 1);
+        assert!(res.is_ok());
+        let one = BigRational::from_integer(1.into());
+        assert_eq!(scope.vars()["y"].0, one);
+
+        let res = eval_line("z = 1; 1/0; z + 1", &opts, &mut scope, 2);
+        assert!(matches!(res, Err(Error::Eval(EvalError::ZeroDivision(_)))));
+        let two = BigRational::from_integer(2.into());
+        assert_eq!(scope.vars()["ans"].0, two);
+
+        let res = eval_line("1+; 2*3", &opts, &mut scope, 3);
+        assert!(matches!(res, Err(Error::Parse(e)) if e.position == 2));
+        let six = BigRational::from_integer(6.into());
+        assert_eq!(scope.vars()["ans"].0, six);
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let input = "\u{feff}:set estimate=+bin\r\n1/3\r:estimate=+lit\r\n";
+        let mut opts = ReplOptions::new();
+        run_lines(input.as_bytes(), &mut opts).unwrap();
+        let expected = vec!["+bin,+lit".to_owned()];
+        assert_eq!(opts.eval, EvalOptions::new().with_estimate(expected));
+
+        let opts = ReplOptions::new();
+        assert!(eval_once("\u{feff}1/3", &opts).is_ok());
+        assert!(eval_once("1/3\r", &opts).is_ok());
+        assert_eq!(normalize_line("\u{feff}1 + 2\r", true), "1 + 2");
+
+        // a BOM past the first line is not one
+        let line = "\u{feff}1 + 2";
+        assert_eq!(normalize_line(line, false), line);
+        let input = "1/3\n\u{feff}1/2\n";
+        let mut out = vec![];
+        let summary =
+            run_batch_lines(input.as_bytes(), &ReplOptions::new(), &mut out)
+                .unwrap();
+        assert_eq!((summary.ok, summary.parse_errors), (1, 1));
+
+        let input = "1\r2\r\n3\r\n";
+        let mut out = vec![];
+        let summary =
+            run_batch_lines(input.as_bytes(), &ReplOptions::new(), &mut out)
+                .unwrap();
+        assert_eq!(summary.ok, 3);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1: 1 = 1 (float: 1.0)\n\
+             2: 2 = 2 (float: 2.0)\n\
+             3: 3 = 3 (float: 3.0)\n"
+        );
+    }
+
+    #[test]
+    fn test_timeout() {
+        let timeout = Some(Duration::from_millis(1));
+        let opts = ReplOptions::new().with_timeout(timeout);
+        let actual = eval_once("10000! + 10000! + 10000!", &opts);
+        assert!(matches!(actual, Err(Error::Eval(EvalError::Timeout(_)))));
+
+        let opts = opts.with_timeout(Some(Duration::from_secs(10)));
+        assert!(eval_once("1/3 + 10!", &opts).is_ok());
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let opts = ReplOptions::new();
+        let deep = "(".repeat(3000) + "1" + &")".repeat(3000);
+        let actual = eval_once(&deep, &opts);
+        let range = DEFAULT_MAX_DEPTH..deep.len() - DEFAULT_MAX_DEPTH;
+        assert!(matches!(
+            actual,
+            Err(Error::Eval(EvalError::TooDeep(r))) if r == range
+        ));
+        let unclosed = "(".repeat(3000);
+        let actual = eval_once(&unclosed, &opts);
+        let range = DEFAULT_MAX_DEPTH..unclosed.len();
+        assert!(matches!(
+            actual,
+            Err(Error::Eval(EvalError::TooDeep(r))) if r == range
+        ));
+
+        // just below the limit, which the stack has room for
+        let n = DEFAULT_MAX_DEPTH - 1;
+        let parens = "(".repeat(n) + "1" + &")".repeat(n);
+        let calls = "abs(".repeat(n) + "1" + &")".repeat(n);
+        let minuses = "-".repeat(n) + "1";
+        for deep in [parens, calls, minuses] {
+            assert!(eval_once(&deep, &opts).is_ok(), "{deep}");
+        }
+
+        // the sums nest to the left, but not in parentheses
+        let sum = vec!["1"; 5000].join(" + ");
+        assert!(eval_once(&sum, &opts).is_ok());
+        let opts = opts.with_max_depth(Some(100));
+        assert!(eval_once(&sum, &opts).is_ok());
+        let fact = "1".to_owned() + &"!".repeat(5000);
+        assert!(eval_once(&fact, &opts).is_ok());
+    }
+
+    #[test]
+    fn test_bindings() {
+        let emacs = bindings(EditMode::Emacs);
+        let vi = bindings(EditMode::Vi);
+        assert_ne!(emacs, vi);
+        assert!(emacs.contains("Ctrl-A") && !vi.contains("Ctrl-A"));
+        assert!(vi.contains("Esc") && !emacs.contains("Esc"));
+        assert!(emacs.contains("Ctrl-R") && vi.contains("Ctrl-R"));
+    }
+}
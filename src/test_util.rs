@@ -0,0 +1,77 @@
+//! Assertions for the tests of the crate and of the embedders, enabled by
+//! the `test-util` feature.
+
+use num_rational::BigRational;
+
+use crate::{ast::EvalOptions, evaluate, number::DecimalTuple};
+
+/// Evaluates `input` and compares it with the expected truth and float,
+/// returning the failure message on a mismatch.
+pub fn check_evaluates_to(
+    input: &str,
+    rational: &str,
+    float: f64,
+) -> Result<(), String> {
+    let expected: BigRational = rational
+        .parse()
+        .map_err(|e| format!("invalid expected rational {rational:?}: {e}"))?;
+    let (rat, flt) = evaluate(input, &EvalOptions::new())
+        .map_err(|e| format!("`{input}` failed to evaluate: {e:?}"))?;
+
+    let same_float = flt == float || (flt.is_nan() && float.is_nan());
+    if rat == expected && same_float {
+        return Ok(());
+    }
+    let dec = |r: &BigRational| DecimalTuple::from(r.to_owned());
+    let mark = |ok| if ok { " " } else { "!" };
+    Err(format!(
+        "`{input}` evaluated to an unexpected value\n\
+         {} truth:    {rat} = {}\n  \
+           expected: {expected} = {}\n\
+         {} float:    {flt:?}\n  \
+           expected: {float:?}",
+        mark(rat == expected),
+        dec(&rat),
+        dec(&expected),
+        mark(same_float),
+    ))
+}
+
+/// Asserts that `input` evaluates to the exact `rational` (e.g. `"1/3"`)
+/// and to the `float`, showing the decimal forms on failure.
+#[track_caller]
+pub fn assert_evaluates_to(input: &str, rational: &str, float: f64) {
+    if let Err(msg) = check_evaluates_to(input, rational, float) {
+        panic!("{msg}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pass() {
+        assert_evaluates_to("1/3 + 1/6", "1/2", 0.5);
+        assert_evaluates_to("0.1 + 0.2", "3/10", 0.30000000000000004);
+        assert_evaluates_to("0 * 1e400", "0", f64::NAN);
+    }
+
+    #[test]
+    fn test_message() {
+        let actual = check_evaluates_to("1/3", "1/4", 1.0 / 3.0);
+        let expected = "`1/3` evaluated to an unexpected value\n\
+                        ! truth:    1/3 = 0.(3...)\n  \
+                          expected: 1/4 = 0.25\n  \
+                          float:    0.3333333333333333\n  \
+                          expected: 0.3333333333333333";
+        assert_eq!(actual, Err(expected.to_owned()));
+
+        let actual = check_evaluates_to("1 +", "1", 1.0).unwrap_err();
+        assert!(actual.starts_with("`1 +` failed to evaluate: Parse"));
+    }
+
+    #[test]
+    #[should_panic(expected = "! float:    0.30000000000000004")]
+    fn test_panic() { assert_evaluates_to("0.1 + 0.2", "3/10", 0.3); }
+}
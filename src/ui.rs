@@ -1,15 +1,30 @@
-use std::ops::Range;
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    ops::Range,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
 
-use combine::{easy::Errors, stream::PointerOffset};
-use num::{FromPrimitive, One, Signed, Zero};
+use combine::easy::Errors;
+use num::{FromPrimitive, One, Signed, ToPrimitive, Zero};
 use num_rational::BigRational;
-use yansi::Style;
+use terminal_size::{terminal_size_of, Width};
+use unicode_width::UnicodeWidthStr;
+use yansi::{Color, Style};
 
 use crate::{
-    ast::{EvalContext, EvalError, EvalOptions, ValueTy},
-    constants::{DARK_COLOR, EMPH_COLOR, ERR_COLOR},
-    number::DecimalTuple,
-    utils::StrPaint,
+    ast::{
+        ErrorUnit, EvalContext, EvalError, EvalOptions, EvalResult,
+        OutputFormat, ValueTy, Warning,
+    },
+    constants::{AUX_COLOR, DARK_COLOR, EMPH_COLOR, ERR_COLOR, OK_COLOR},
+    number::{
+        continued_fraction, ilog10, round_rational, to_base_string_bounded,
+        to_base_string_truncated, to_scientific, value_at_precision,
+        DecimalTuple, RoundingMode,
+    },
+    utils::{char_column, StrPaint},
 };
 
 fn str_emph_correct(approx: &DecimalTuple, truth: &DecimalTuple) -> String {
@@ -20,94 +35,499 @@ fn str_emph_correct(approx: &DecimalTuple, truth: &DecimalTuple) -> String {
     };
 
     if len < s.len() {
-        format!("{}{}", s[..len].bold(), s[len..].fg(DARK_COLOR))
+        format!("{}{}", s[..len].bold(), s[len..].fg(palette().dark))
     } else if approx.is_integer() {
         let s0 = format!("{0:0<1$}", s + ".", len);
-        format!("{}{}", s0.bold(), "(0...)".fg(DARK_COLOR))
+        format!("{}{}", s0.bold(), "(0...)".fg(palette().dark))
     } else {
         let s0 = format!("{0:0<1$}", s, len);
-        format!("{}{}", s0.bold(), "(0...)".fg(DARK_COLOR))
+        format!("{}{}", s0.bold(), "(0...)".fg(palette().dark))
     }
 }
 
-fn str_approx(approx: &DecimalTuple, truth: &DecimalTuple) -> String {
-    let rat_approx = BigRational::from(approx.to_owned());
-    let rat_truth = BigRational::from(truth.to_owned());
-
-    let t = if truth.is_repetitive() {
-        rat_truth.to_string()
-    } else {
-        truth.to_string()
-    };
-
-    if rat_approx == rat_truth {
-        return t;
-    }
-
-    let abs = &rat_approx - &rat_truth;
-    let rel = &abs / &rat_truth;
+/// The sign and the magnitude of the relative error of `approx` to `truth`,
+/// e.g. `('-', "2^{-54}")`.
+fn str_rel_error(approx: &BigRational, truth: &BigRational) -> (char, String) {
+    let abs = approx - truth;
+    let rel = &abs / truth;
     let num_tz = rel.numer().trailing_zeros().unwrap_or(0) as i32;
     let den_tz = rel.denom().trailing_zeros().unwrap_or(0) as i32;
     let exp = num_tz - den_tz;
     let rel = rel.abs() / BigRational::from_i32(2).unwrap().pow(exp);
 
-    if rel.is_one() {
-        format!(
-            "{t} * (1 {} {})",
-            if abs.is_positive() { '+' } else { '-' },
-            if exp == 0 { "1".to_owned() } else { format!("2^{{{exp}}}") }
-        )
+    let sgn = if abs.is_positive() { '+' } else { '-' };
+    let mag = if rel.is_one() {
+        if exp == 0 { "1".to_owned() } else { format!("2^{{{exp}}}") }
     } else {
         format!(
-            "{t} * (1 {} {rel}{})",
-            if abs.is_positive() { '+' } else { '-' },
+            "{rel}{}",
             if exp == 0 { "".to_owned() } else { format!(" * 2^{{{exp}}}") }
         )
+    };
+    (sgn, mag)
+}
+
+fn str_approx(approx: &BigRational, truth: &BigRational) -> String {
+    // a terminating decimal if any, otherwise the fraction
+    let t = match DecimalTuple::from_rational(truth.to_owned(), 0) {
+        Some(truth) => truth.to_string(),
+        None => truth.to_string(),
+    };
+
+    if approx == truth {
+        return t;
+    }
+
+    let (sgn, mag) = str_rel_error(approx, truth);
+    format!("{t} * (1 {sgn} {mag})")
+}
+
+/// Width of the terminal the reports go to, or `None` if stderr is not a
+/// terminal.
+pub fn terminal_width() -> Option<usize> {
+    terminal_size_of(std::io::stderr()).map(|(Width(w), _)| w as usize)
+}
+
+/// The colors of the panels, each of which can be overridden by a 256-color
+/// code in an environment variable, e.g. `FEATHER_EMPH_COLOR=13`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Palette {
+    pub emph: Color,
+    pub aux: Color,
+    pub dark: Color,
+    pub err: Color,
+    pub ok: Color,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            emph: EMPH_COLOR,
+            aux: AUX_COLOR,
+            dark: DARK_COLOR,
+            err: ERR_COLOR,
+            ok: OK_COLOR,
+        }
+    }
+}
+
+impl Palette {
+    /// The environment variables, in the order of `colors`.
+    pub const VARS: [&'static str; 5] = [
+        "FEATHER_EMPH_COLOR",
+        "FEATHER_AUX_COLOR",
+        "FEATHER_DARK_COLOR",
+        "FEATHER_ERR_COLOR",
+        "FEATHER_OK_COLOR",
+    ];
+
+    pub fn colors(&self) -> [Color; 5] {
+        [self.emph, self.aux, self.dark, self.err, self.ok]
+    }
+
+    fn colors_mut(&mut self) -> [&mut Color; 5] {
+        [
+            &mut self.emph,
+            &mut self.aux,
+            &mut self.dark,
+            &mut self.err,
+            &mut self.ok,
+        ]
+    }
+
+    /// The palette with the colors given by `var`, which looks up the
+    /// variables. An invalid value keeps the default with a warning.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let mut res = Self::default();
+        for (name, color) in Self::VARS.into_iter().zip(res.colors_mut()) {
+            let Some(value) = var(name) else { continue };
+            match value.trim().parse() {
+                Ok(code) => *color = Color::Fixed(code),
+                Err(_) => eprintln!(
+                    "warning: ignoring {name}={value:?}, \
+                     which is not a color code from 0 to 255"
+                ),
+            }
+        }
+        res
+    }
+
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+}
+
+static PALETTE: OnceLock<Palette> = OnceLock::new();
+
+/// Sets the palette for the rest of the process. This has no effect once
+/// the palette has been set or used.
+pub fn set_palette(palette: Palette) { let _ = PALETTE.set(palette); }
+
+/// The palette in use, the default one unless set by `set_palette`.
+pub fn palette() -> &'static Palette { PALETTE.get_or_init(Palette::default) }
+
+/// A sample of each color in the palette with its fixed code.
+pub fn color_test() -> String {
+    let mut out = String::new();
+    for (name, color) in Palette::VARS.into_iter().zip(palette().colors()) {
+        let code = match color {
+            Color::Fixed(code) => code.to_string(),
+            _ => "-".to_owned(),
+        };
+        let sample = color.paint("0.1 + 0.2 = 0.30000000000000004");
+        let bold = color.style().bold().paint("bold");
+        out += &format!("{name:<18} {code:>3}  {sample}  {bold}\n");
+    }
+    out
+}
+
+/// Maps `f64` to integers in the same order, adjacent representable values
+/// to adjacent integers, and both zeros to 0.
+fn ordered_bits(x: f64) -> i64 {
+    let b = x.to_bits() as i64;
+    if b < 0 { i64::MIN - b } else { b }
+}
+
+fn ulp_offset(x: f64, n: i64) -> f64 {
+    let k = ordered_bits(x) + n;
+    f64::from_bits(if k < 0 { (i64::MIN - k) as u64 } else { k as u64 })
+}
+
+fn cmp_float(x: f64, truth: &BigRational) -> Ordering {
+    match BigRational::from_float(x) {
+        Some(x) => x.cmp(truth),
+        None if x > 0.0 => Ordering::Greater,
+        None => Ordering::Less,
+    }
+}
+
+/// The largest `f64` not above `truth` and the smallest not below it,
+/// which are infinite beyond `f64::MAX`.
+pub fn neighbors(truth: &BigRational) -> (f64, f64) {
+    let mut lo = truth.to_f64().filter(|x| !x.is_nan()).unwrap_or(0.0);
+    while cmp_float(lo, truth).is_gt() {
+        lo = ulp_offset(lo, -1);
+    }
+    while cmp_float(ulp_offset(lo, 1), truth).is_le() {
+        lo = ulp_offset(lo, 1);
+    }
+    let hi = if cmp_float(lo, truth).is_eq() { lo } else { ulp_offset(lo, 1) };
+    (lo, hi)
+}
+
+/// The correctly rounded `f64` of `truth`, with ties to even.
+fn nearest_float(truth: &BigRational) -> f64 {
+    let (lo, hi) = neighbors(truth);
+    if lo == hi || hi.is_infinite() {
+        return lo;
+    } else if lo.is_infinite() {
+        return hi;
+    }
+    let d_lo = truth - BigRational::from_float(lo).unwrap();
+    let d_hi = BigRational::from_float(hi).unwrap() - truth;
+    match d_lo.cmp(&d_hi) {
+        Ordering::Less => lo,
+        Ordering::Greater => hi,
+        Ordering::Equal if lo.to_bits() % 2 == 0 => lo,
+        Ordering::Equal => hi,
+    }
+}
+
+/// The number of representable steps from the correctly rounded value of
+/// `truth` to `flt`, and on which side of `truth` the `flt` lies.
+fn ulp_distance(flt: f64, truth: &BigRational) -> (u64, Ordering) {
+    let nearest = nearest_float(truth);
+    let dist = ordered_bits(flt).abs_diff(ordered_bits(nearest));
+    (dist, cmp_float(flt, truth))
+}
+
+/// The relative error in ppm with 3 significant digits, and the number of
+/// correct significant digits, i.e. `floor(-log10(|rel|))`, or 0 if the
+/// error exceeds the truth.
+fn str_err_ppm(rel: &BigRational) -> String {
+    if rel.is_zero() {
+        return "exact".to_owned();
+    }
+    let ppm = rel * BigRational::from_integer(1_000_000.into());
+    let places = (2 - ilog10(&ppm.abs())).max(0) as usize;
+    let ppm: DecimalTuple =
+        round_rational(&ppm, places, RoundingMode::HalfUp).into();
+
+    let e = ilog10(&rel.abs());
+    let exact_pow = rel.abs() == BigRational::from_integer(10.into()).pow(e);
+    let digits = (-e - if exact_pow { 0 } else { 1 }).max(0);
+    format!("{ppm} ppm, ≈ {digits} correct significant digits")
+}
+
+/// The relative error as `f64`; infinite if only the truth is zero.
+fn rel_error(flt: f64, truth: &BigRational) -> f64 {
+    let flt = BigRational::from_float(flt).unwrap();
+    if truth.is_zero() {
+        return if flt.is_zero() { 0.0 } else { f64::INFINITY };
+    }
+    ((flt - truth) / truth).to_f64().unwrap()
+}
+
+/// The relative error of the finite `flt` in `unit`.
+fn str_error(flt: f64, truth: &BigRational, unit: ErrorUnit) -> String {
+    let rel = rel_error(flt, truth);
+    match unit {
+        ErrorUnit::Ratio => format!("{rel:e}"),
+        ErrorUnit::Percent => format!("{:e} %", rel * 100.0),
+        ErrorUnit::Ulp => {
+            let (dist, _) = ulp_distance(flt, truth);
+            format!("{dist} ulp{}", if dist == 1 { "" } else { "s" })
+        }
+        ErrorUnit::Db => format!("{:.2} dB", 20.0 * rel.abs().log10()),
     }
 }
 
 pub fn frontmatter(filename: &str, lineno: usize) {
-    eprintln!(
-        "\n{}{filename}:{lineno}{}",
-        " ╭─[".fg(DARK_COLOR),
-        "]".fg(DARK_COLOR)
-    );
+    eprint!("{}", str_frontmatter(filename, lineno));
+}
+
+pub fn str_frontmatter(filename: &str, lineno: usize) -> String {
+    format!(
+        "\n{}{filename}:{lineno}{}\n",
+        " ╭─[".fg(palette().dark),
+        "]".fg(palette().dark)
+    )
+}
+
+pub fn backmatter(s: &str, result: EvalResult, warnings: &[Warning]) {
+    eprint!("{}", str_backmatter(s, &result, warnings));
 }
 
-pub fn backmatter(s: &str, result: Result<(ValueTy, Range<usize>), EvalError>) {
+/// The warnings and the error if any, closing the panel.
+pub fn str_backmatter(
+    s: &str,
+    result: &EvalResult,
+    warnings: &[Warning],
+) -> String {
+    let mut res = String::new();
+    for warning in warnings {
+        let (color, label) = if warning.kind.is_note() {
+            (palette().ok, "note")
+        } else {
+            (palette().aux, "warning")
+        };
+        let style = color.style().bold();
+        let span = warning.span.clone();
+        let msg = format!("{label}: {}", warning.kind);
+        let mut out = "\n".to_owned();
+        out += &match &warning.op {
+            Some(op) => {
+                let focus = (palette().err.style().bold(), op.clone());
+                s.paint_focus_msg(style, span, focus, &msg)
+            }
+            None => s.paint_range_msg(style, span, &msg),
+        };
+        res += &lined(&out, |i| {
+            if i == 1 { color.style() } else { color.style().dimmed() }
+        });
+    }
     match result {
         Ok(_) => {
-            eprintln!("{}", "─╯".fg(DARK_COLOR).dimmed());
+            res += &format!("{}\n", "─╯".fg(palette().dark).dimmed());
         }
         Err(e) => {
+            let (range, msg) = eval_error_msg(e);
             let mut out = "\n".to_owned();
-            out += &match e {
-                EvalError::ZeroDivision(range) => s.paint_range_msg(
-                    ERR_COLOR.style().bold(),
-                    range,
-                    "divide by zero",
-                ),
-            };
-            lined(&out, |i| {
+            out += &s.paint_range_msg(palette().err.style().bold(), range, msg);
+            res += &lined(&out, |i| {
                 if i == 1 {
-                    ERR_COLOR.style()
+                    palette().err.style()
                 } else {
-                    ERR_COLOR.style().dimmed()
+                    palette().err.style().dimmed()
                 }
             });
-            eprintln!("{}", "─╯".fg(ERR_COLOR).dimmed());
+            res += &format!("{}\n", "─╯".fg(palette().err).dimmed());
         }
     }
+    res
 }
 
-fn lined(lines: &str, style: impl Fn(usize) -> Style) {
+/// The range and the description of the error.
+pub fn eval_error_msg(e: &EvalError) -> (Range<usize>, &'static str) {
+    match e {
+        EvalError::ZeroDivision(range) => (range.clone(), "divide by zero"),
+        EvalError::DomainError(range, msg) => (range.clone(), msg),
+        EvalError::Timeout(range) => (range.clone(), "timed out"),
+        EvalError::Undefined(range) => (range.clone(), "undefined variable"),
+        EvalError::BadLiteral(range) => (range.clone(), "malformed literal"),
+        EvalError::TooDeep(range) => (range.clone(), "nested too deeply"),
+    }
+}
+
+fn lined(lines: &str, style: impl Fn(usize) -> Style) -> String {
+    let mut res = String::new();
     for (i, line) in lines.lines().enumerate() {
-        eprintln!(" {} {line}", style(i).paint("│"));
+        res += &format!(" {} {line}\n", style(i).paint("│"));
+    }
+    res
+}
+
+fn str_float_special(x: f64) -> String {
+    if x.is_nan() {
+        "nan".to_owned()
+    } else if x.is_sign_positive() {
+        "infinity".to_owned()
+    } else {
+        "-infinity".to_owned()
     }
 }
 
+/// The exact decimal value of the float.
+fn str_float_exact(x: f64) -> String {
+    match BigRational::from_float(x) {
+        Some(rat) => DecimalTuple::from(rat).to_string(),
+        None => str_float_special(x),
+    }
+}
+
+/// The C99 hexadecimal form, e.g. `0x1.5p+3` for 10.5. Subnormals are
+/// written as `0x0.<mantissa>p-1022`.
+fn str_hex_float(x: f64) -> String {
+    if x.is_nan() {
+        return "nan".to_owned();
+    }
+    let sign = if x.is_sign_negative() { "-" } else { "" };
+    if x.is_infinite() {
+        return format!("{sign}inf");
+    } else if x == 0.0 {
+        return format!("{sign}0x0p+0");
+    }
+
+    let bits = x.to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    let mant = bits & ((1 << 52) - 1);
+    let (lead, exp) = if biased == 0 { (0, -1022) } else { (1, biased - 1023) };
+    let frac = format!("{mant:013x}");
+    let frac = frac.trim_end_matches('0');
+    if frac.is_empty() {
+        format!("{sign}0x{lead}p{exp:+}")
+    } else {
+        format!("{sign}0x{lead}.{frac}p{exp:+}")
+    }
+}
+
+/// The float as an odd integer times a power of two, e.g.
+/// `3602879701896397 * 2^-55` for 0.1, which is what the float exactly is.
+fn str_float_dyadic(x: f64) -> String {
+    if !x.is_finite() {
+        return str_float_special(x);
+    }
+    let sign = if x.is_sign_negative() { "-" } else { "" };
+    if x == 0.0 {
+        return format!("{sign}0");
+    }
+
+    let bits = x.to_bits();
+    let biased = ((bits >> 52) & 0x7ff) as i32;
+    let mant = bits & ((1 << 52) - 1);
+    let (mant, exp) = if biased == 0 {
+        (mant, -1074)
+    } else {
+        (mant | 1 << 52, biased - 1075)
+    };
+    let tz = mant.trailing_zeros();
+    format!("{sign}{} * 2^{}", mant >> tz, exp + tz as i32)
+}
+
+/// Runs of a digit shorter than this are left as they are.
+const MIN_GROUPED_RUN: usize = 5;
+
+/// Collapses the runs of a digit into `(d×n)`, e.g. `0.1(0×8)1` for
+/// `0.1000000001`. The `×` tells them apart from the repeating part.
+fn str_grouped(s: &str) -> String {
+    let mut res = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut run = 1;
+        while c.is_ascii_digit() && chars.peek() == Some(&c) {
+            chars.next();
+            run += 1;
+        }
+        if run >= MIN_GROUPED_RUN {
+            res += &format!("({c}×{run})");
+        } else {
+            res.extend(std::iter::repeat_n(c, run));
+        }
+    }
+    res
+}
+
+/// Separates the leading integer digits of the formatted `s` into groups
+/// of three by `sep`, e.g. `-1,234,567.5(3...)`.
+fn str_thousands(s: &str, sep: char) -> String {
+    let digits = s.trim_start_matches('-');
+    let sign = &s[..s.len() - digits.len()];
+    let end =
+        digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+    let (int, rest) = digits.split_at(end);
+    let mut res = sign.to_owned();
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            res.push(sep);
+        }
+        res.push(c);
+    }
+    res + rest
+}
+
+/// Formats as a mixed number, e.g. `2 1/3` for 7/3 and `-2 1/3` for -7/3.
+fn str_mixed(rat: &BigRational) -> String {
+    let (int, frac) = (rat.to_integer(), rat.fract().abs());
+    if frac.is_zero() {
+        int.to_string()
+    } else if int.is_zero() {
+        rat.to_string()
+    } else {
+        format!("{int} {frac}")
+    }
+}
+
+/// Formats as `[a0; a1, a2]`, or `[a0]` for an integer, abbreviating the
+/// terms after the first `max_terms` ones with `...`.
+fn str_cont_frac(rat: &BigRational, max_terms: Option<usize>) -> String {
+    let cf = continued_fraction(rat);
+    let len = max_terms.map_or(cf.len(), |n| n.clamp(1, cf.len()));
+    let mut rest: Vec<_> = cf[1..len].iter().map(|a| a.to_string()).collect();
+    if len < cf.len() {
+        rest.push("...".to_owned());
+    }
+    if rest.is_empty() {
+        format!("[{}]", cf[0])
+    } else {
+        format!("[{}; {}]", cf[0], rest.join(", "))
+    }
+}
+
+thread_local! {
+    /// Time spent in `estimate`, to be excluded from the evaluation time.
+    static REPORT_TIME: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+/// Returns and resets the time spent in reporting the estimates.
+pub fn take_report_time() -> Duration { REPORT_TIME.take() }
+
+thread_local! {
+    /// The reports of `estimate` collected by `capture_estimates`, if any.
+    static CAPTURED: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with the estimates collected in the pretty format rather than
+/// printed, and returns them along with its result.
+pub fn capture_estimates<T>(f: impl FnOnce() -> T) -> (T, String) {
+    let outer = CAPTURED.replace(Some(String::new()));
+    let res = f();
+    let captured = CAPTURED.replace(outer).unwrap_or_default();
+    (res, captured)
+}
+
 pub fn estimate(
     expr: &ValueTy,
+    flt32: Option<f32>,
     range: Range<usize>,
     s: &str,
     opts: &EvalOptions,
@@ -116,86 +536,460 @@ pub fn estimate(
     if !opts.do_estimate(ctx) {
         return;
     }
+    let captured = CAPTURED.with_borrow_mut(|captured| {
+        let report = || estimate_report(expr, range.clone(), s, opts, flt32);
+        captured.as_mut().map(|captured| captured.push_str(&report()))
+    });
+    if captured.is_some() {
+        return;
+    }
+    let start = Instant::now();
+    match opts.format() {
+        OutputFormat::Pretty => {
+            eprint!("{}", estimate_report(expr, range, s, opts, flt32))
+        }
+        OutputFormat::Markdown => {
+            print!("{}", report_markdown(expr, range, s, opts))
+        }
+        // only the whole statement, by `report_json`
+        OutputFormat::Json => {}
+    }
+    REPORT_TIME.set(REPORT_TIME.get() + start.elapsed());
+}
+
+pub fn timings(parsed: Duration, evaluated: Duration) {
+    eprintln!("{}", str_timings(parsed, evaluated));
+}
+
+pub fn str_timings(parsed: Duration, evaluated: Duration) -> String {
+    format!("parsed in {parsed:?}, evaluated in {evaluated:?}")
+        .fg(palette().dark)
+        .to_string()
+}
 
+pub fn estimate_report(
+    expr: &ValueTy,
+    range: Range<usize>,
+    s: &str,
+    opts: &EvalOptions,
+    flt32: Option<f32>,
+) -> String {
     let (rat, flt) = expr;
 
     let msg = format!(
         "{}: {:?}\n",
         Style::default().bold().paint("{this:?}"),
-        EMPH_COLOR.style().bold().paint(flt)
+        palette().emph.style().bold().paint(flt)
     );
 
     let mut out = "\n".to_owned();
-    out += &format!(
-        "{}",
-        s.paint_range_msg(EMPH_COLOR.style().bold(), range, &msg)
-    );
+    out += &s.paint_range_msg(palette().emph.style().bold(), range, &msg);
 
     out += "\n";
-    out += &format!("truth: {rat}\n");
-    if !rat.is_integer() {
-        out += &format!("     = {}\n", DecimalTuple::from(rat.to_owned()));
+    let sep = opts.group_separator();
+    if opts.show_group() && rat.is_integer() {
+        out += &format!("truth: {}\n", str_thousands(&rat.to_string(), sep));
+    } else {
+        out += &format!("truth: {rat}\n");
+    }
+    let improper = rat.numer().abs() > rat.denom().abs();
+    if opts.mixed_fractions() && !rat.is_integer() && improper {
+        out += &format!("     = {}\n", str_mixed(rat));
+    }
+    let (d_rat, too_long) = truth_decimal(rat, opts.max_period());
+    let sci = opts
+        .show_sci()
+        .then(|| to_scientific(rat, opts.max_period()))
+        .flatten()
+        .filter(|(_, e)| e.abs() >= SCI_EXPONENT);
+    if let Some((m, e)) = sci {
+        let m = match opts.digit_limit() {
+            Some(limit) => m.to_string_limited(limit),
+            None => m.to_string(),
+        };
+        out += &format!("     = {m}e{e}\n");
+    } else if !rat.is_integer() {
+        let (eq, d_rat) = match (opts.digits(), opts.digit_limit()) {
+            (Some(places), _) => {
+                let exact = !too_long
+                    && d_rat.period() == 0
+                    && d_rat.preperiod() <= places;
+                let eq = if exact { '=' } else { '≈' };
+                (eq, d_rat.to_decimal_string(places, RoundingMode::HalfUp))
+            }
+            _ if too_long => {
+                let limit = opts.digit_limit().unwrap_or(TRUNCATED_SHOWN);
+                let d_rat =
+                    d_rat.to_decimal_string(limit, RoundingMode::Truncate);
+                ('=', format!("{d_rat}... (period too long to show)"))
+            }
+            (None, Some(limit)) => ('=', d_rat.to_string_limited(limit)),
+            (None, None) => ('=', d_rat.to_string()),
+        };
+        let d_rat =
+            if opts.show_group() { str_thousands(&d_rat, sep) } else { d_rat };
+        let d_rat =
+            if opts.group_repeats() { str_grouped(&d_rat) } else { d_rat };
+        out += &format!("     {eq} {d_rat}\n");
     }
 
-    let d_rat = DecimalTuple::from(rat.to_owned());
-    let f = if flt.is_nan() {
-        "nan".to_owned()
-    } else if flt.is_infinite() {
-        (if flt.is_positive() { "infinity" } else { "-infinity" }).to_owned()
+    if let Some(base) = opts.base() {
+        out += &format!(" base: {}\n", str_base(rat, base, opts));
+    }
+
+    if opts.show_mixed() {
+        out += &format!("mixed: {}\n", str_mixed(rat));
+    }
+
+    if opts.show_period() && too_long {
+        out += &format!("cycle: > {}\n", opts.max_period());
+    } else if opts.show_period() {
+        out += &format!("cycle: {}\n", d_rat.period());
+    }
+    let f = if !flt.is_finite() {
+        str_float_special(*flt)
     } else if *flt == 0.0 && flt.is_sign_negative() {
         // note: to produce -0.0 without the unary minus, e.g.
         // `1 / ((0 - 1) / (1e20 + 1 - 1e20))`.
-        "-0".to_owned()
+        str_neg_zero(opts)
     } else {
         let d_flt = DecimalTuple::from(BigRational::from_float(*flt).unwrap());
         str_emph_correct(&d_flt, &d_rat)
     };
     out += &format!("float: {}\n", f);
     if !rat.is_zero() && flt.is_finite() && !flt.is_zero() {
-        let d_flt = DecimalTuple::from(BigRational::from_float(*flt).unwrap());
-        out += &format!("     = {}\n", str_approx(&d_flt, &d_rat));
+        let r_flt = BigRational::from_float(*flt).unwrap();
+        out += &format!("     = {}\n", str_approx(&r_flt, rat));
+    }
+    if flt.is_finite() {
+        let (dist, side) = ulp_distance(*flt, rat);
+        let side = match side {
+            _ if dist == 0 => "",
+            Ordering::Greater => " (above)",
+            _ => " (below)",
+        };
+        out += &format!(" ulps: {dist}{side}\n");
+    }
+    if let Some(places) = opts.rounding().filter(|_| !rat.is_integer()) {
+        let (trunc, round) = value_at_precision(rat, places);
+        out += &format!("trunc: {trunc}\nround: {round}\n");
+    }
+    if let Some(flt32) = flt32 {
+        // the relative error goes through the exact rational of the `f32`,
+        // rather than the `f64` widened from it
+        let r_flt32 = BigRational::from_float(flt32);
+        let f = match &r_flt32 {
+            None => str_float_special(flt32.into()),
+            Some(_) if flt32 == 0.0 && flt32.is_sign_negative() => {
+                str_neg_zero(opts)
+            }
+            Some(r_flt32) => {
+                str_emph_correct(&r_flt32.to_owned().into(), &d_rat)
+            }
+        };
+        out += &format!("float32: {f}\n");
+        let nonzero = !rat.is_zero() && flt32 != 0.0;
+        if let Some(r_flt32) = r_flt32.filter(|_| nonzero) {
+            out += &format!("        = {}\n", str_approx(&r_flt32, rat));
+        }
+    }
+    if let Some(unit) = opts.error_unit().filter(|_| flt.is_finite()) {
+        out += &format!("error: {}\n", str_error(*flt, rat, unit));
+    }
+    if opts.show_err() && flt.is_finite() && !rat.is_zero() {
+        let rel = (BigRational::from_float(*flt).unwrap() - rat) / rat;
+        out += &format!("  err: {}\n", str_err_ppm(&rel));
+    }
+    if opts.show_hex() {
+        out += &format!("  hex: {}\n", str_hex_float(*flt));
+    }
+    if opts.dyadic() {
+        out += &format!("exact: {}\n", str_float_dyadic(*flt));
+    }
+    if opts.show_neighbors() {
+        let (lo, hi) = neighbors(rat);
+        out += &format!(" prev: {}\n", str_float_exact(lo));
+        out += &format!(" next: {}\n", str_float_exact(hi));
+    }
+    if opts.show_cont_frac() && !rat.is_integer() {
+        out += &format!("   cf: {}\n", str_cont_frac(rat, opts.max_terms()));
     }
 
-    lined(&out, |i| {
-        if i == 1 { DARK_COLOR.style() } else { DARK_COLOR.style().dimmed() }
-    });
+    let dark = palette().dark;
+    lined(&out, |i| if i == 1 { dark.style() } else { dark.style().dimmed() })
+}
+
+/// Fractional digits the truth is truncated at when its period is too long,
+/// which are more than the exact decimal of any float has.
+const TRUNCATED_PLACES: usize = 1100;
+
+/// Fractional digits shown of such a truth, unless limited otherwise.
+const TRUNCATED_SHOWN: usize = 50;
+
+/// Smallest `|e|` of the truth `m * 10^e` written as such with `+sci`.
+const SCI_EXPONENT: i32 = 6;
+
+/// The truth as `DecimalTuple`, and whether it is truncated because its
+/// period is longer than `max_period`.
+fn truth_decimal(rat: &BigRational, max_period: usize) -> (DecimalTuple, bool) {
+    match DecimalTuple::from_rational(rat.to_owned(), max_period) {
+        Some(dec) => (dec, false),
+        None => {
+            let mode = RoundingMode::Truncate;
+            (round_rational(rat, TRUNCATED_PLACES, mode).into(), true)
+        }
+    }
+}
+
+/// `-0`, or `0` with `EvalOptions::with_compact_zero`.
+fn str_neg_zero(opts: &EvalOptions) -> String {
+    if opts.compact_zero() { "0" } else { "-0" }.to_owned()
+}
+
+/// `rat` in `base`, prefixed as in Rust literals where there is a prefix,
+/// e.g. `0b0.(01...)` for 1/3, and truncated as the decimal if its period
+/// is longer than `opts.max_period()`.
+fn str_base(rat: &BigRational, base: u32, opts: &EvalOptions) -> String {
+    let (s, note) = match to_base_string_bounded(rat, base, opts.max_period()) {
+        Some(s) => (s, ""),
+        None => {
+            let limit = opts.digit_limit().unwrap_or(TRUNCATED_SHOWN);
+            let s = to_base_string_truncated(rat, base, limit);
+            (s + "...", " (period too long to show)")
+        }
+    };
+    let (sign, mag) = match s.strip_prefix('-') {
+        Some(mag) => ("-", mag),
+        None => ("", s.as_str()),
+    };
+    match base {
+        2 => format!("{sign}0b{mag}{note}"),
+        8 => format!("{sign}0o{mag}{note}"),
+        16 => format!("{sign}0x{mag}{note}"),
+        _ => format!("{s} (base {base}){note}"),
+    }
+}
+
+/// The decimal of the truth, truncated if its period is too long.
+fn str_truth_decimal(rat: &BigRational, max_period: usize) -> String {
+    match truth_decimal(rat, max_period) {
+        (dec, false) => dec.to_string(),
+        (dec, true) => {
+            let mode = RoundingMode::Truncate;
+            format!("{}...", dec.to_decimal_string(TRUNCATED_SHOWN, mode))
+        }
+    }
+}
+
+/// The expression in inline code and the head of the table that
+/// `report_markdown` fills.
+pub fn markdown_header(s: &str) -> String {
+    format!(
+        "`{}`\n\n| input | exact | decimal | float | error |\n\
+         |---|---|---|---|---|\n",
+        s.trim()
+    )
+}
+
+/// The parse error of `s` in place of the table, at the column in
+/// characters as by `--batch`.
+pub fn markdown_parse_error(
+    s: &str,
+    err: &Errors<char, String, usize>,
+) -> String {
+    format!("> parse error at column {}\n\n", char_column(s, err.position))
+}
+
+/// A color-free table row for the subexpression `s[range]`.
+pub fn report_markdown(
+    expr: &ValueTy,
+    range: Range<usize>,
+    s: &str,
+    opts: &EvalOptions,
+) -> String {
+    let (rat, flt) = expr;
+    let decimal = str_truth_decimal(rat, opts.max_period());
+    let error = match BigRational::from_float(*flt) {
+        Some(f) if f == *rat => "0".to_owned(),
+        Some(f) if !rat.is_zero() => {
+            let (sgn, mag) = str_rel_error(&f, rat);
+            format!("`{sgn}{mag}`")
+        }
+        _ => "-".to_owned(),
+    };
+    format!("| `{}` | {rat} | {decimal} | {flt:?} | {error} |\n", &s[range])
+}
+
+/// Warnings and errors following the table, as blockquotes.
+pub fn markdown_footer(
+    s: &str,
+    result: &EvalResult,
+    warnings: &[Warning],
+) -> String {
+    let mut out = "\n".to_owned();
+    for warning in warnings {
+        let span = &s[warning.span.clone()];
+        let label = if warning.kind.is_note() { "note" } else { "warning" };
+        out += &format!("> {label}: {} at `{span}`\n\n", warning.kind);
+    }
+    if let Err(e) = result {
+        let (range, msg) = eval_error_msg(e);
+        out += &format!("> error: {msg} at `{}`\n\n", &s[range]);
+    }
+    out
 }
 
-pub fn error_report(err: Errors<char, &str, PointerOffset<str>>, s: &str) {
-    let pos = err.position.translate_position(s);
+/// `s` as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut res = "\"".to_owned();
+    for c in s.chars() {
+        match c {
+            '"' => res += "\\\"",
+            '\\' => res += "\\\\",
+            '\n' => res += "\\n",
+            c if c.is_control() => res += &format!("\\u{:04x}", c as u32),
+            c => res.push(c),
+        }
+    }
+    res + "\""
+}
+
+/// A single-line JSON object of the fields, whose values are JSON already.
+fn json_object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|(k, v)| format!("{}: {v}", json_string(k)))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+/// The statement `s[range]` without the surrounding spaces, and the byte
+/// offset of it in `s`.
+fn json_statement(s: &str, range: Range<usize>) -> [(&'static str, String); 2] {
+    let stmt = &s[range.clone()];
+    let offset = range.start + (stmt.len() - stmt.trim_start().len());
+    [("input", json_string(stmt.trim())), ("offset", offset.to_string())]
+}
+
+/// The result of the statement `s[range]` as a JSON object. The numbers
+/// are strings, to be read without loss; `float_decimal` is the exact
+/// decimal of the float, or `null` if it is not finite. An error comes
+/// with its byte range, and the warnings with theirs, both in `s`.
+pub fn report_json(
+    s: &str,
+    range: Range<usize>,
+    result: &EvalResult,
+    warnings: &[Warning],
+    opts: &EvalOptions,
+) -> String {
+    let mut fields = json_statement(s, range).to_vec();
+    match result {
+        Ok(((rat, flt), _)) => {
+            let float_decimal = BigRational::from_float(*flt)
+                .map(|f| json_string(&DecimalTuple::from(f).to_string()));
+            let truth_decimal = str_truth_decimal(rat, opts.max_period());
+            let rational = format!("{}/{}", rat.numer(), rat.denom());
+            fields.extend([
+                ("rational", json_string(&rational)),
+                ("float", json_string(&format!("{flt:?}"))),
+                ("float_decimal", float_decimal.unwrap_or("null".to_owned())),
+                ("truth_decimal", json_string(&truth_decimal)),
+            ]);
+        }
+        Err(e) => {
+            let (range, msg) = eval_error_msg(e);
+            fields.push(("error", json_string(msg)));
+            fields.push(("span", format!("[{}, {}]", range.start, range.end)));
+        }
+    }
+    let warnings: Vec<_> = warnings
+        .iter()
+        .map(|warning| {
+            let label = if warning.kind.is_note() { "note" } else { "warning" };
+            let span = &warning.span;
+            json_object(&[
+                ("kind", json_string(label)),
+                ("message", json_string(&warning.kind.to_string())),
+                ("span", format!("[{}, {}]", span.start, span.end)),
+            ])
+        })
+        .collect();
+    fields.push(("warnings", format!("[{}]", warnings.join(", "))));
+    json_object(&fields)
+}
+
+/// The parse error of the statement `s[range]` as a JSON object, with the
+/// byte offset in `s`.
+pub fn json_parse_error(
+    s: &str,
+    range: Range<usize>,
+    err: &Errors<char, String, usize>,
+) -> String {
+    let pos = err.position;
+    let mut fields = json_statement(s, range).to_vec();
+    fields.extend([
+        ("error", json_string("parse error")),
+        ("span", format!("[{pos}, {pos}]")),
+    ]);
+    json_object(&fields)
+}
+
+pub fn error_report(err: &Errors<char, String, usize>, s: &str) {
+    eprint!("{}", str_error_report(err, s));
+}
+
+/// The parse error, closing the panel.
+pub fn str_error_report(err: &Errors<char, String, usize>, s: &str) -> String {
+    let pos = err.position;
     let eof = if pos >= s.len() {
-        "$".fg(DARK_COLOR).dimmed().to_string()
+        "$".fg(palette().dark).dimmed().to_string()
     } else {
         "".to_owned()
     };
+    // the display column, e.g. 1 for `π` and 2 for `１`
+    let col = s[..pos.min(s.len())].width();
     let mut out = vec![
         "".to_owned(),
-        format!("{}{eof}", s.paint_at(ERR_COLOR.style().bold(), pos)),
-        format!("{0:>1$}", "┬".fg(ERR_COLOR), pos + 1),
-        format!("{0:>1$}", "╰── parse error".fg(ERR_COLOR), pos + 15),
+        format!("{}{eof}", s.paint_at(palette().err.style().bold(), pos)),
+        format!("{0:>1$}", "┬".fg(palette().err), col + 1),
+        format!("{0:>1$}", "╰── parse error".fg(palette().err), col + 15),
         "".to_owned(),
-        format!("{}", "errors:".fg(DARK_COLOR)),
+        format!("{}", "errors:".fg(palette().dark)),
     ];
-    for e in err.errors {
+    for e in &err.errors {
         out.push(format!(
             " {}  {}",
-            "*".fg(DARK_COLOR).dimmed(),
-            e.to_string().fg(DARK_COLOR)
+            "*".fg(palette().dark).dimmed(),
+            e.to_string().fg(palette().dark)
         ));
     }
     let out: String = out.join("\n");
-    lined(&out, |i| match i {
-        0 => DARK_COLOR.style().dimmed(),
-        1 => ERR_COLOR.style(),
-        _ => ERR_COLOR.style().dimmed(),
+    let mut res = lined(&out, |i| match i {
+        0 => palette().dark.style().dimmed(),
+        1 => palette().err.style(),
+        _ => palette().err.style().dimmed(),
     });
-    eprintln!("{}", "─╯".fg(ERR_COLOR).dimmed());
+    res += &format!("{}\n", "─╯".fg(palette().err).dimmed());
+    res
 }
 
 #[cfg(test)]
 mod tests {
+    use combine::EasyParser;
+
     use super::*;
+    use crate::ast::WarningKind;
+    use crate::constants::MAX_DIGITS;
+    use crate::parser::{parse_line, translate_errors};
 
+    /// The report of `s`, whose value is `s` itself as a fraction and `flt`
+    /// as a float.
+    fn report(s: &str, flt: f64, opts: &EvalOptions) -> String {
+        let val = (s.parse().unwrap(), flt);
+        estimate_report(&val, 0..s.len(), s, opts, None)
+    }
+
+    #[allow(clippy::type_complexity)]
     const TEST_SUITE: &[((&str, &str), (&str, &str))] = &[
         (("1.23", "1.24"), ("1.2", "3")),
         (("1.2", "1.3"), ("1.", "2")),
@@ -212,11 +1006,563 @@ mod tests {
             let approx = approx.parse().unwrap();
             let truth = truth.parse().unwrap();
             let actual = str_emph_correct(&approx, &truth);
-            let expected = format!("{}{}", bold.bold(), dark.fg(DARK_COLOR));
+            let expected =
+                format!("{}{}", bold.bold(), dark.fg(palette().dark));
             assert_eq!(
                 actual, expected,
                 "\nactual:   {actual}\nexpected: {expected}"
             );
         }
     }
+
+    #[test]
+    fn test_cont_frac() {
+        let opts = EvalOptions::new();
+        let actual = report("355/113", 355.0 / 113.0, &opts);
+        assert!(!actual.contains("cf:"));
+
+        let opts = opts.with_estimate(vec!["+cf".to_owned()]);
+        let actual = report("355/113", 355.0 / 113.0, &opts);
+        assert!(actual.contains("cf: [3; 7, 16]"));
+    }
+
+    #[test]
+    fn test_cont_frac_max_terms() {
+        let rat = "355/113".parse().unwrap();
+        assert_eq!(str_cont_frac(&rat, Some(3)), "[3; 7, 16]");
+        assert_eq!(str_cont_frac(&rat, Some(2)), "[3; 7, ...]");
+        assert_eq!(str_cont_frac(&rat, None), "[3; 7, 16]");
+
+        let test_suite = [
+            ("22/7", "[3; 7]"),
+            ("1/1", "[1]"),
+            ("3", "[3]"),
+            ("-7/3", "[-3; 1, 2]"),
+            ("0", "[0]"),
+        ];
+        for (rat, expected) in test_suite {
+            assert_eq!(str_cont_frac(&rat.parse().unwrap(), None), expected);
+        }
+
+        // consecutive Fibonacci numbers yield a long run of ones
+        let rat = "832040/514229".parse().unwrap();
+        assert_eq!(str_cont_frac(&rat, Some(4)), "[1; 1, 1, 1, ...]");
+        assert_eq!(str_cont_frac(&rat, Some(1)), "[1; ...]");
+    }
+
+    #[test]
+    fn test_width() {
+        use crate::constants::DEFAULT_WIDTH;
+        assert_eq!(EvalOptions::new().width(), DEFAULT_WIDTH);
+        assert_eq!(EvalOptions::new().with_width(None).width(), 80);
+        assert_eq!(EvalOptions::new().with_width(Some(120)).width(), 120);
+    }
+
+    #[test]
+    fn test_ulps() {
+        let rat = |s: &str| s.parse::<BigRational>().unwrap();
+        let min_sub = f64::from_bits(1);
+
+        assert_eq!(ulp_distance(1.0 / 3.0, &rat("1/3")), (0, Ordering::Less));
+        assert_eq!(ulp_distance(0.1 + 0.2, &rat("3/10")).0, 1);
+        assert_eq!(ulp_distance(0.1 + 0.2, &rat("3/10")).1, Ordering::Greater);
+        assert_eq!(ulp_distance(0.5, &rat("1/2")), (0, Ordering::Equal));
+        // across zero, and among subnormals
+        assert_eq!(ulp_distance(-min_sub, &rat("0")), (1, Ordering::Less));
+        assert_eq!(ulp_distance(min_sub, &rat("0")), (1, Ordering::Greater));
+        let truth = BigRational::from_float(min_sub * 3.0).unwrap();
+        assert_eq!(ulp_distance(-min_sub, &truth), (4, Ordering::Less));
+
+        let actual = report("3/10", 0.1 + 0.2, &EvalOptions::new());
+        assert!(actual.contains(" ulps: 1 (above)\n"));
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let rat = |s: &str| s.parse::<BigRational>().unwrap();
+        let (lo, hi) = neighbors(&rat("1/10"));
+        assert_eq!((lo, hi), (ulp_offset(0.1, -1), 0.1));
+        assert_eq!(neighbors(&rat("1/2")), (0.5, 0.5));
+        assert_eq!(neighbors(&rat("0")), (0.0, 0.0));
+
+        let big = BigRational::from_float(f64::MAX).unwrap() * rat("2");
+        assert_eq!(neighbors(&big), (f64::MAX, f64::INFINITY));
+        assert_eq!(neighbors(&-big), (f64::NEG_INFINITY, f64::MIN));
+
+        let min_sub = f64::from_bits(1);
+        let tiny = BigRational::from_float(min_sub).unwrap() / rat("3");
+        assert_eq!(neighbors(&tiny), (0.0, min_sub));
+    }
+
+    #[test]
+    fn test_neighbors_report() {
+        let opts = EvalOptions::new();
+        let actual = report("1/2", 0.5, &opts);
+        assert!(!actual.contains("prev:"));
+
+        let opts = opts.with_estimate(vec!["+ulp".to_owned()]);
+        let actual = report("1/2", 0.5, &opts);
+        assert!(actual.contains(" prev: 0.5\n"));
+        assert!(actual.contains(" next: 0.5\n"));
+
+        let s = format!("1{}", "0".repeat(400));
+        let actual = report(&s, f64::INFINITY, &opts);
+        assert!(actual.contains(" next: infinity\n"));
+    }
+
+    #[test]
+    fn test_error_unit() {
+        let rat = |s: &str| s.parse::<BigRational>().unwrap();
+        let db = |flt, truth| {
+            let s = str_error(flt, &truth, ErrorUnit::Db);
+            s.strip_suffix(" dB").unwrap().parse::<f64>().unwrap()
+        };
+        assert!((db(1.001, rat("1")) + 60.0).abs() < 1e-2);
+        assert!((db(0.9, rat("1")) + 20.0).abs() < 1e-2);
+        assert!((db(2.0, rat("1")) - 0.0).abs() < 1e-2);
+        assert_eq!(str_error(0.5, &rat("1/2"), ErrorUnit::Db), "-inf dB");
+        assert_eq!(str_error(0.0, &rat("0"), ErrorUnit::Db), "-inf dB");
+        assert_eq!(str_error(1e-300, &rat("0"), ErrorUnit::Db), "inf dB");
+
+        assert_eq!(str_error(1.5, &rat("1"), ErrorUnit::Ratio), "5e-1");
+        assert_eq!(str_error(1.5, &rat("1"), ErrorUnit::Percent), "5e1 %");
+        assert_eq!(str_error(0.1 + 0.2, &rat("3/10"), ErrorUnit::Ulp), "1 ulp");
+    }
+
+    #[test]
+    fn test_hex_float() {
+        let test_suite = [
+            (10.5, "0x1.5p+3"),
+            (1.0, "0x1p+0"),
+            (-0.1, "-0x1.999999999999ap-4"),
+            (f64::MAX, "0x1.fffffffffffffp+1023"),
+            (f64::MIN_POSITIVE, "0x1p-1022"),
+            (f64::from_bits(1), "0x0.0000000000001p-1022"),
+            (0.0, "0x0p+0"),
+            (-0.0, "-0x0p+0"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+            (f64::NAN, "nan"),
+        ];
+        for (x, expected) in test_suite {
+            assert_eq!(str_hex_float(x), expected);
+        }
+    }
+
+    #[test]
+    fn test_float_dyadic() {
+        let test_suite = [
+            (0.5, "1 * 2^-1"),
+            (0.1, "3602879701896397 * 2^-55"),
+            (-6.0, "-3 * 2^1"),
+            (1.0, "1 * 2^0"),
+            (f64::MAX, "9007199254740991 * 2^971"),
+            (f64::MIN_POSITIVE, "1 * 2^-1022"),
+            (f64::from_bits(3), "3 * 2^-1074"),
+            (0.0, "0"),
+            (-0.0, "-0"),
+            (f64::INFINITY, "infinity"),
+            (f64::NAN, "nan"),
+        ];
+        for (x, expected) in test_suite {
+            assert_eq!(str_float_dyadic(x), expected);
+        }
+
+        let opts = EvalOptions::new();
+        let actual = report("1/10", 0.1, &opts);
+        assert!(!actual.contains("exact:"));
+        let opts = opts.with_dyadic(true);
+        let actual = report("1/10", 0.1, &opts);
+        assert!(actual.contains("exact: 3602879701896397 * 2^-55\n"));
+    }
+
+    #[test]
+    fn test_period() {
+        let opts = EvalOptions::new().with_estimate(vec!["+period".into()]);
+        let test_suite = [
+            ("1/7", 1.0 / 7.0, "cycle: 6\n"),
+            ("1/6", 1.0 / 6.0, "cycle: 1\n"),
+            ("1", 1.0, "cycle: 0\n"),
+        ];
+        for (s, flt, expected) in test_suite {
+            assert!(report(s, flt, &opts).contains(expected), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_max_period() {
+        // the period of 1/1000003 is 1000002 digits long
+        let (s, flt) = ("1/1000003", 1.0 / 1000003.0);
+        let opts = EvalOptions::new()
+            .with_max_period(Some(1000))
+            .with_estimate(vec!["+period".into()]);
+        let actual = report(s, flt, &opts);
+        let expected = format!(
+            "     = 0.{}... (period too long to show)\n",
+            &"000000999997000008999973000080999757000728997813006"[..50]
+        );
+        assert!(actual.contains(&expected), "{actual}");
+        assert!(actual.contains("cycle: > 1000\n"));
+        assert!(actual.contains("     = 1/1000003 * (1 "));
+        assert!(actual.contains(" ulps: 0\n"));
+
+        let opts = opts.with_digit_limit(Some(8));
+        let actual = report(s, flt, &opts);
+        assert!(actual.contains("     = 0.00000099... (period too long"));
+    }
+
+    #[test]
+    fn test_compact_zero() {
+        let s = "-0";
+        let opts = EvalOptions::new();
+        assert!(report(s, -0.0, &opts).contains("float: -0\n"));
+        let opts = opts.with_compact_zero(true);
+        let val = (BigRational::zero(), -0.0);
+        let actual = estimate_report(&val, 0..s.len(), s, &opts, Some(-0.0));
+        assert!(actual.contains("float: 0\n"));
+        assert!(actual.contains("float32: 0\n"));
+    }
+
+    #[test]
+    fn test_float32() {
+        let s = "1/3";
+        let opts = EvalOptions::new();
+        assert!(!report(s, 1.0 / 3.0, &opts).contains("float32:"));
+
+        let val = (s.parse().unwrap(), 1.0 / 3.0);
+        let actual =
+            estimate_report(&val, 0..s.len(), s, &opts, Some(1.0_f32 / 3.0));
+        let rat32 = BigRational::from_float(1.0_f32 / 3.0).unwrap();
+        let d_flt32 = DecimalTuple::from(rat32);
+        let emph = str_emph_correct(&d_flt32, &"0.(3)".parse().unwrap());
+        assert!(actual.contains(&format!("float32: {emph}\n")));
+        assert!(actual.contains("        = 1/3 * (1 + 2^{-25})\n"));
+    }
+
+    #[test]
+    fn test_err_ppm() {
+        let rat = |s: &str| s.parse::<BigRational>().unwrap();
+        assert_eq!(ilog10(&rat("1")), 0);
+        assert_eq!(ilog10(&rat("999/1000")), -1);
+        assert_eq!(ilog10(&rat("1/1000")), -3);
+        assert_eq!(ilog10(&rat("12345")), 4);
+        assert_eq!(ilog10(&rat("1/7")), -1);
+
+        let test_suite = [
+            ("1/1000", "1000 ppm, ≈ 3 correct significant digits"),
+            ("-1/3000", "-333 ppm, ≈ 3 correct significant digits"),
+            ("1/2", "500000 ppm, ≈ 0 correct significant digits"),
+            ("1/7000000", "0.143 ppm, ≈ 6 correct significant digits"),
+            // a float of 16 against a truth of 3
+            ("13/3", "4333333 ppm, ≈ 0 correct significant digits"),
+            ("10", "10000000 ppm, ≈ 0 correct significant digits"),
+            ("0", "exact"),
+        ];
+        for (rel, expected) in test_suite {
+            assert_eq!(str_err_ppm(&rat(rel)), expected);
+        }
+
+        // 0.1 + 0.2 is off by 1/3 * 2^{-51}
+        let rel = rat("1/3") / BigRational::from_integer(2.into()).pow(51);
+        let actual = str_err_ppm(&rel);
+        assert!(actual.ends_with("≈ 15 correct significant digits"));
+    }
+
+    #[test]
+    fn test_digit_limit() {
+        let opts = EvalOptions::new().with_digit_limit(Some(3));
+        let actual = report("1/7", 1.0 / 7.0, &opts);
+        assert!(actual.contains("     = 0.142(857142...)\n"));
+    }
+
+    #[test]
+    fn test_digits() {
+        let opts = EvalOptions::new().with_digits(Some(5));
+        let test_suite = [
+            ("1/7", 1.0 / 7.0, "     ≈ 0.14286\n"),
+            ("1/8", 0.125, "     = 0.12500\n"),
+            ("-2/3", -2.0 / 3.0, "     ≈ -0.66667\n"),
+        ];
+        for (s, flt, expected) in test_suite {
+            assert!(report(s, flt, &opts).contains(expected), "{s}");
+        }
+
+        let opts = EvalOptions::new().with_digits(Some(usize::MAX));
+        assert_eq!(opts.digits(), Some(MAX_DIGITS));
+        let opts = EvalOptions::new().with_rounding(Some(usize::MAX));
+        assert_eq!(opts.rounding(), Some(MAX_DIGITS));
+    }
+
+    #[test]
+    fn test_base() {
+        let test_suite = [
+            ("1/3", 2, "0b0.(01...)"),
+            ("-3/2", 16, "-0x1.8"),
+            ("8", 8, "0o10"),
+            ("1/3", 3, "0.1 (base 3)"),
+        ];
+        let opts = EvalOptions::new();
+        for (rat, base, expected) in test_suite {
+            let actual = str_base(&rat.parse().unwrap(), base, &opts);
+            assert_eq!(actual, expected);
+        }
+
+        // the period of 1/1000000007 in base 2 is too long to find
+        let rat = "1/1000000007".parse().unwrap();
+        let opts = opts.with_max_period(Some(1000)).with_digit_limit(Some(8));
+        let actual = str_base(&rat, 2, &opts);
+        assert_eq!(actual, "0b0.00000000... (period too long to show)");
+        let actual = str_base(&rat, 3, &opts);
+        assert_eq!(actual, "0.00000000... (base 3) (period too long to show)");
+
+        let opts = EvalOptions::new().with_base(Some(2));
+        let actual = report("1/10", 0.1, &opts);
+        assert!(actual.contains(" base: 0b0.0(0011...)\n"));
+
+        for base in [0, 1, 37] {
+            let opts = EvalOptions::new().with_base(Some(base));
+            assert_eq!(opts.base(), None);
+            assert!(!report("1/10", 0.1, &opts).contains(" base: "));
+        }
+    }
+
+    #[test]
+    fn test_mixed_fractions() {
+        let opts = EvalOptions::new().with_mixed_fractions(true);
+        let test_suite = [
+            ("7/2", "     = 3 1/2\n", true),
+            ("-7/2", "     = -3 1/2\n", true),
+            ("1/2", "     = 1/2\n", false),
+            ("3", "     = 3\n", false),
+        ];
+        for (s, line, shown) in test_suite {
+            assert_eq!(report(s, 0.0, &opts).contains(line), shown, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_color_test() {
+        let out = color_test();
+        for code in ["201", "3", "246", "9", "10"] {
+            let line = format!(" {code:>3}  ");
+            assert!(out.contains(&line), "{code}");
+        }
+        assert_eq!(out.lines().count(), 5);
+    }
+
+    #[test]
+    fn test_palette_from_vars() {
+        let vars = [
+            ("FEATHER_EMPH_COLOR", "13"),
+            ("FEATHER_DARK_COLOR", " 240 "),
+            ("FEATHER_ERR_COLOR", "red"),
+            ("FEATHER_OK_COLOR", "256"),
+        ];
+        let var = |name: &str| {
+            vars.iter().find(|v| v.0 == name).map(|v| v.1.to_owned())
+        };
+        let expected = Palette {
+            emph: Color::Fixed(13),
+            dark: Color::Fixed(240),
+            ..Palette::default()
+        };
+        assert_eq!(Palette::from_vars(var), expected);
+        assert_eq!(Palette::from_vars(|_| None), Palette::default());
+    }
+
+    #[test]
+    fn test_group_repeats() {
+        let test_suite = [
+            ("0.1000000001", "0.1(0×8)1"),
+            ("0.10001", "0.10001"),
+            ("-1000000.5(00000...)", "-1(0×6).5((0×5)...)"),
+            ("0.(9999999...)", "0.((9×7)...)"),
+        ];
+        for (s, expected) in test_suite {
+            assert_eq!(str_grouped(s), expected);
+        }
+
+        let (s, flt) = ("1000000001/10000000000", 0.1000000001);
+        let opts = EvalOptions::new();
+        let actual = report(s, flt, &opts);
+        assert!(actual.contains("     = 0.1000000001\n"));
+        let opts = opts.with_group_repeats(true);
+        let actual = report(s, flt, &opts);
+        assert!(actual.contains("     = 0.1(0×8)1\n"));
+    }
+
+    #[test]
+    fn test_timings() {
+        let (parsed, evaluated) =
+            (Duration::from_micros(15), Duration::from_millis(2));
+        assert!(str_timings(parsed, evaluated)
+            .contains("parsed in 15µs, evaluated in 2ms"));
+
+        REPORT_TIME.set(evaluated);
+        assert_eq!(take_report_time(), evaluated);
+        assert_eq!(take_report_time(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_thousands() {
+        let test_suite = [
+            ("1000000000000", ',', "1,000,000,000,000"),
+            ("-1234567.5(3...)", ',', "-1,234,567.5(3...)"),
+            ("123.4567", ',', "123.4567"),
+            ("0.(142857...)", ',', "0.(142857...)"),
+            ("12345", '_', "12_345"),
+        ];
+        for (s, sep, expected) in test_suite {
+            assert_eq!(str_thousands(s, sep), expected);
+        }
+
+        let opts = EvalOptions::new().with_estimate(vec!["+group".into()]);
+        let actual = report("1000000000000", 1e12, &opts);
+        assert!(actual.contains("truth: 1,000,000,000,000\n"), "{actual}");
+        let opts = opts.with_group_separator(Some(' '));
+        let actual = report("2469135/2", 1234567.5, &opts);
+        assert!(actual.contains("truth: 2469135/2\n"), "{actual}");
+        assert!(actual.contains("     = 1 234 567.5\n"), "{actual}");
+    }
+
+    #[test]
+    fn test_sci() {
+        let opts = EvalOptions::new().with_estimate(vec!["+sci".into()]);
+        let actual = report("1/1000000000000", 1e-12, &opts);
+        assert!(actual.contains("     = 1e-12\n"), "{actual}");
+        assert!(!actual.contains("     = 0.000000000001\n"), "{actual}");
+
+        let actual = report("2000000000000/3", 2e12 / 3.0, &opts);
+        assert!(actual.contains("     = 6.(6...)e11\n"), "{actual}");
+
+        // in range, or without `+sci`
+        let actual = report("1/8", 0.125, &opts);
+        assert!(actual.contains("     = 0.125\n"), "{actual}");
+        let actual = report("1/1000000000000", 1e-12, &EvalOptions::new());
+        assert!(actual.contains("     = 0.000000000001\n"), "{actual}");
+    }
+
+    #[test]
+    fn test_mixed() {
+        let test_suite = [
+            ("7/3", "2 1/3"),
+            ("-7/3", "-2 1/3"),
+            ("1/3", "1/3"),
+            ("-1/3", "-1/3"),
+            ("6/3", "2"),
+            ("-5", "-5"),
+            ("0", "0"),
+        ];
+        for (rat, expected) in test_suite {
+            assert_eq!(str_mixed(&rat.parse().unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn test_rounding() {
+        let opts = EvalOptions::new().with_rounding(Some(3));
+        let actual = report("2/3", 2.0 / 3.0, &opts);
+        assert!(actual.contains("trunc: 0.666\n"));
+        assert!(actual.contains("round: 0.667\n"));
+    }
+
+    #[test]
+    fn test_markdown() {
+        let s = "1/3";
+        let val = (s.parse().unwrap(), 1.0 / 3.0);
+        let actual = report_markdown(&val, 0..s.len(), s, &EvalOptions::new());
+        assert_eq!(
+            actual,
+            "| `1/3` | 1/3 | 0.(3...) | 0.3333333333333333 | `-2^{-54}` |\n"
+        );
+        assert!(!actual.contains('\x1b'));
+
+        let s = "0.5";
+        let val = ("1/2".parse().unwrap(), 0.5);
+        let actual = report_markdown(&val, 0..s.len(), s, &EvalOptions::new());
+        assert_eq!(actual, "| `0.5` | 1/2 | 0.5 | 0.5 | 0 |\n");
+    }
+
+    #[test]
+    fn test_json() {
+        let s = "1/10 * 3";
+        let val = ("3/10".parse().unwrap(), 0.1 * 3.0);
+        let opts = EvalOptions::new();
+        let actual =
+            report_json(s, 0..s.len(), &Ok((val, 0..s.len())), &[], &opts);
+        assert_eq!(
+            actual,
+            "{\"input\": \"1/10 * 3\", \"offset\": 0, \"rational\": \"3/10\", \
+             \"float\": \"0.30000000000000004\", \
+             \"float_decimal\": \"0.3000000000000000444089209850062616169452667236328125\", \
+             \"truth_decimal\": \"0.3\", \"warnings\": []}"
+        );
+
+        let s = "1; 1/0";
+        let err = EvalError::ZeroDivision(3..6);
+        let actual = report_json(s, 2..6, &Err(err), &[], &opts);
+        assert_eq!(
+            actual,
+            r#"{"input": "1/0", "offset": 3, "error": "divide by zero", "span": [3, 6], "warnings": []}"#
+        );
+
+        let s = "x = 1e300; x * x";
+        let big = BigRational::from_integer(10.into()).pow(600);
+        let val = (big, f64::INFINITY);
+        let kind = WarningKind::Overflow;
+        let warnings = [Warning { kind, span: 11..16, op: Some(13..14) }];
+        let actual =
+            report_json(s, 10..16, &Ok((val, 11..16)), &warnings, &opts);
+        assert!(actual.starts_with(r#"{"input": "x * x", "offset": 11, "#));
+        assert!(actual.ends_with(
+            r#""warnings": [{"kind": "warning", "message": "overflow", "span": [11, 16]}]}"#
+        ));
+
+        let s = "1; \"1\" +";
+        let err = parse_line().easy_parse(&s[3..]).unwrap_err();
+        let err = translate_errors(err, s);
+        assert_eq!(
+            json_parse_error(s, 2..s.len(), &err),
+            r#"{"input": "\"1\" +", "offset": 3, "error": "parse error", "span": [3, 3]}"#
+        );
+        assert_eq!(json_string("a\\b\n\u{7}"), r#""a\\b\n\u0007""#);
+    }
+
+    #[test]
+    fn test_markdown_parse_error() {
+        // U+3000 takes 3 bytes but a column, as in `--batch`
+        let s = "1 +\u{3000})";
+        let err = parse_line().easy_parse(s).unwrap_err();
+        let err = translate_errors(err, s);
+        let expected = "> parse error at column 5\n\n";
+        assert_eq!(markdown_parse_error(s, &err), expected);
+    }
+
+    #[test]
+    fn test_markdown_note() {
+        let s = "(1/3) * 3";
+        let val = ("1".parse().unwrap(), 1.0);
+        let kind = WarningKind::ExactDespiteInexact;
+        let warnings = [Warning { kind, span: 0..s.len(), op: None }];
+        let actual = markdown_footer(s, &Ok((val, 0..s.len())), &warnings);
+        assert_eq!(
+            actual,
+            "\n> note: exact despite inexact intermediates at `(1/3) * 3`\n\n"
+        );
+    }
+
+    #[test]
+    fn test_error_report_multibyte() {
+        let re = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        // `π` is 2 bytes and 1 column, `１` is 3 bytes and 2 columns
+        let test_suite = [("π + ", 5, 4), ("１ + )", 6, 5), ("1 + ", 4, 4)];
+        for (s, pos, col) in test_suite {
+            let actual = str_error_report(&Errors::empty(pos), s);
+            let actual = re.replace_all(&actual, "");
+            let lines: Vec<_> = actual.lines().collect();
+            assert_eq!(lines[2], format!(" │ {}┬", " ".repeat(col)), "{s}");
+            let expected = format!(" │ {}╰── parse error", " ".repeat(col));
+            assert_eq!(lines[3], expected, "{s}");
+        }
+    }
 }
@@ -1,7 +1,48 @@
-use std::ops::Range;
+use std::{ops::Range, panic, sync::OnceLock, thread};
 
+use combine::stream::PointerOffset;
+use regex::Regex;
+use unicode_width::UnicodeWidthStr;
 use yansi::{Color, Paint, Style};
 
+use crate::constants::STACK_SIZE;
+
+/// Runs `f` on a thread with `STACK_SIZE` of stack, as the parser and the
+/// evaluation recurse as deep as the input nests. A panic in `f` is resumed
+/// on the caller.
+pub fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        let handle = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .expect("failed to spawn the evaluating thread");
+        handle.join().unwrap_or_else(|e| panic::resume_unwind(e))
+    })
+}
+
+/// Converts the range of pointers into `s` into that of byte offsets.
+pub fn translate_range(
+    range: &Range<PointerOffset<str>>,
+    s: &str,
+) -> Range<usize> {
+    let start = range.start.translate_position(s);
+    let end = range.end.translate_position(s);
+    debug_assert!(start <= end, "reversed range: {start}..{end}");
+    start..end
+}
+
+/// The 1-based column, in characters, of the byte offset `pos` into `s`.
+pub fn char_column(s: &str, pos: usize) -> usize {
+    s[..pos].chars().count() + 1
+}
+
+/// The width of `s` in display columns, without the ANSI color sequences.
+pub fn display_width(s: &str) -> usize {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new("\x1b\\[[0-9;]*m").unwrap());
+    re.replace_all(s, "").width()
+}
+
 pub trait StrPaint {
     fn fg(&self, paint: Color) -> Paint<&Self> { paint.paint(self) }
     fn bold(&self) -> Paint<&Self> { Style::default().bold().paint(self) }
@@ -13,10 +54,25 @@ pub trait StrPaint {
         range: Range<usize>,
         msg: &str,
     ) -> String;
+    /// Same as `paint_range_msg`, but paints `focus` within `range` in its
+    /// own style.
+    fn paint_focus_msg(
+        &self,
+        style: Style,
+        range: Range<usize>,
+        focus: (Style, Range<usize>),
+        msg: &str,
+    ) -> String;
 }
 
 impl StrPaint for str {
+    /// Paints the character at the byte offset `i`, or the one containing
+    /// it.
     fn paint_at(&self, style: Style, i: usize) -> String {
+        let mut i = i.min(self.len());
+        while !self.is_char_boundary(i) {
+            i -= 1;
+        }
         let mut res = self[..i].to_owned();
         let mut it = self[i..].chars();
         res.extend(it.next().map(|c| style.paint(c).to_string()));
@@ -30,35 +86,63 @@ impl StrPaint for str {
         range: Range<usize>,
         msg: &str,
     ) -> String {
-        let Range { start, end } = range;
-        let mut res = format!(
+        let Range { start, end } = range.clone();
+        let res = format!(
             "{}{}{}\n",
             &self[..start],
             style.paint(&self[start..end]),
             &self[end..]
         );
-        let left = if end - start > 2 { 1 } else { 0 };
-        let right = end - start - (left + 1);
-        let line1 = format!(
-            "{0}{1}{2}{3}",
-            " ".repeat(start),
-            "─".repeat(left),
-            "┬",
-            "─".repeat(right)
-        );
-        let line2 = format!(
-            "{0}{1}{2}{3}",
-            " ".repeat(start),
-            " ".repeat(left),
-            "╰",
-            "─".repeat(2)
+        res + &underline(self, style, range, msg)
+    }
+
+    fn paint_focus_msg(
+        &self,
+        style: Style,
+        range: Range<usize>,
+        (focus_style, focus): (Style, Range<usize>),
+        msg: &str,
+    ) -> String {
+        let Range { start, end } = range.clone();
+        debug_assert!(start <= focus.start && focus.end <= end);
+        let res = format!(
+            "{}{}{}{}{}\n",
+            &self[..start],
+            style.paint(&self[start..focus.start]),
+            focus_style.paint(&self[focus.clone()]),
+            style.paint(&self[focus.end..end]),
+            &self[end..]
         );
-        let color = Style::default().fg(style.fg_color()).bg(style.bg_color());
-        res += &format!("{}\n{} {msg}", color.paint(line1), color.paint(line2));
-        res
+        res + &underline(self, style, range, msg)
     }
 }
 
+/// The two lines pointing at `range` of `s` from below, ending with `msg`.
+fn underline(s: &str, style: Style, range: Range<usize>, msg: &str) -> String {
+    let Range { start, end } = range;
+    // in display columns, at least one for `┬` even if empty
+    let indent = s[..start].width();
+    let width = s[start..end].width().max(1);
+    let left = if width > 2 { 1 } else { 0 };
+    let right = width - (left + 1);
+    let line1 = format!(
+        "{0}{1}{2}{3}",
+        " ".repeat(indent),
+        "─".repeat(left),
+        "┬",
+        "─".repeat(right)
+    );
+    let line2 = format!(
+        "{0}{1}{2}{3}",
+        " ".repeat(indent),
+        " ".repeat(left),
+        "╰",
+        "─".repeat(2)
+    );
+    let color = Style::default().fg(style.fg_color()).bg(style.bg_color());
+    format!("{}\n{} {msg}", color.paint(line1), color.paint(line2))
+}
+
 pub trait IterDiffIndex<T: PartialEq>: Iterator<Item = T> {
     fn iter_diff_index<I: Iterator<Item = T>>(self, other: I) -> Option<usize>;
 }
@@ -75,12 +159,27 @@ pub fn cycle_mu_lambda<T: PartialEq>(
     x0: T,
     f: impl Fn(&T) -> T,
 ) -> (usize, usize) {
+    cycle_mu_lambda_bounded(x0, f, usize::MAX).unwrap()
+}
+
+/// Similar to `cycle_mu_lambda`, but gives up if the cycle is not met
+/// within `limit` steps, which is the case when `mu + lambda > limit`.
+pub fn cycle_mu_lambda_bounded<T: PartialEq>(
+    x0: T,
+    f: impl Fn(&T) -> T,
+    limit: usize,
+) -> Option<(usize, usize)> {
     let mut tor = f(&x0);
     let mut har = f(&tor);
 
+    let mut steps = 1;
     while tor != har {
+        if steps >= limit {
+            return None;
+        }
         tor = f(&tor);
         har = f(&f(&har));
+        steps += 1;
     }
 
     let mut tor = x0;
@@ -98,5 +197,105 @@ pub fn cycle_mu_lambda<T: PartialEq>(
         lambda += 1;
     }
 
-    (mu, lambda)
+    Some((mu, lambda))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ptr_range(s: &str, range: Range<usize>) -> Range<PointerOffset<str>> {
+        let base = s.as_ptr() as usize;
+        PointerOffset::new(base + range.start)
+            ..PointerOffset::new(base + range.end)
+    }
+
+    #[test]
+    fn test_translate_range() {
+        let s = "1 + 23";
+        assert_eq!(translate_range(&ptr_range(s, 4..6), s), 4..6);
+        assert_eq!(translate_range(&ptr_range(s, 0..0), s), 0..0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "reversed range")]
+    fn test_translate_range_reversed() {
+        let s = "1 + 23";
+        #[allow(clippy::reversed_empty_ranges)]
+        translate_range(&ptr_range(s, 4..2), s);
+    }
+
+    #[test]
+    fn test_paint_at() {
+        let style = Style::default().bold();
+        let s = "π + 1";
+        assert_eq!(s.paint_at(style, 0), format!("{} + 1", style.paint('π')));
+        assert_eq!(s.paint_at(style, 1), s.paint_at(style, 0));
+        assert_eq!(s.paint_at(style, 3), format!("π {} 1", style.paint('+')));
+        assert_eq!(s.paint_at(style, s.len()), s);
+    }
+
+    #[test]
+    fn test_paint_range_msg() {
+        let re = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        let test_suite = [
+            ("1 + 2", 2..3, ["1 + 2", "  ┬", "  ╰── msg"]),
+            ("1 + 23", 4..6, ["1 + 23", "    ┬─", "    ╰── msg"]),
+            ("10 * 100", 5..8, ["10 * 100", "     ─┬─", "      ╰── msg"]),
+            ("π − 1", 3..6, ["π − 1", "  ┬", "  ╰── msg"]),
+            ("π − 1", 0..8, ["π − 1", "─┬───", " ╰── msg"]),
+            ("1", 1..1, ["1", " ┬", " ╰── msg"]),
+        ];
+        for (s, range, expected) in test_suite {
+            let style = Style::default().bold();
+            let actual = s.paint_range_msg(style, range, "msg");
+            let actual = re.replace_all(&actual, "");
+            assert_eq!(actual.lines().collect::<Vec<_>>(), expected, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_paint_focus_msg() {
+        let s = "1 * 2";
+        let style = Style::default().dimmed();
+        let focus = Style::default().bold();
+        let actual = s.paint_focus_msg(style, 0..5, (focus, 2..3), "msg");
+        let expected = format!(
+            "{}{}{}\n",
+            style.paint("1 "),
+            focus.paint("*"),
+            style.paint(" 2")
+        );
+        assert!(actual.starts_with(&expected), "{actual:?}");
+        assert!(actual.ends_with("╰── msg"), "{actual:?}");
+    }
+
+    #[test]
+    fn test_char_column() {
+        assert_eq!(char_column("1 + )", 4), 5);
+        // U+3000 takes 3 bytes
+        assert_eq!(char_column("1 +\u{3000})", 6), 5);
+        assert_eq!(char_column("", 0), 1);
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("1 + 2"), 5);
+        assert_eq!(display_width("π − １"), 6);
+        let painted = Style::default().bold().paint("1/3").to_string();
+        assert_eq!(display_width(&format!(" │ {painted}")), 6);
+    }
+
+    #[test]
+    fn test_cycle_mu_lambda_bounded() {
+        // 1/7: remainders 1, 3, 2, 6, 4, 5, 1, ...
+        let f = |x: &u32| x * 10 % 7;
+        assert_eq!(cycle_mu_lambda(1, f), (0, 6));
+        assert_eq!(cycle_mu_lambda_bounded(1, f, 6), Some((0, 6)));
+        assert_eq!(cycle_mu_lambda_bounded(1, f, 5), None);
+        // 1/12: remainders 1, 10, 4, 4, ...
+        let f = |x: &u32| x * 10 % 12;
+        assert_eq!(cycle_mu_lambda_bounded(1, f, 3), Some((2, 1)));
+    }
 }
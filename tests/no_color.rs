@@ -0,0 +1,34 @@
+//! Colors are global to the process, so this runs apart from the unit tests
+//! that compare painted strings.
+
+use feather_repl::{
+    ast::EvalOptions,
+    constants::DEFAULT_MAX_DEPTH,
+    parser::parse_line_checked,
+    ui::{
+        color_test, estimate_report, str_backmatter, str_error_report,
+        str_frontmatter,
+    },
+};
+
+#[test]
+fn test_no_color() {
+    yansi::Paint::disable();
+    let opts = EvalOptions::new().with_estimate(vec!["+ulp,+cancel".into()]);
+    let mut out = str_frontmatter("stdin", 1) + &color_test();
+    for s in ["1.0001 - 1", "1e300 * 1e300", "1/0"] {
+        let expr = parse_line_checked(s, false, DEFAULT_MAX_DEPTH).unwrap();
+        let (res, warnings) = expr.eval_with_warnings(s, &opts.silenced());
+        if let Ok((val, range)) = &res {
+            out += &estimate_report(val, range.clone(), s, &opts, None);
+        }
+        out += &str_backmatter(s, &res, &warnings);
+    }
+    let s = "1 + ";
+    out += &str_error_report(
+        &parse_line_checked(s, false, DEFAULT_MAX_DEPTH).unwrap_err(),
+        s,
+    );
+    assert!(out.contains("warning: overflow"), "{out}");
+    assert!(!out.contains('\x1b'), "{out}");
+}